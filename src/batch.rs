@@ -0,0 +1,190 @@
+use std::{
+    fs::read_to_string,
+    io::{self, stdin},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use tokio::{
+    sync::{Mutex, Semaphore},
+    task::{JoinSet, LocalSet},
+};
+use tracing::{error, info};
+
+use crate::{
+    cfg::Config,
+    cli::{BuilderFunction, Opts},
+    fetcher::Clients,
+    frontend::headless_batch,
+    run,
+};
+
+#[derive(Debug, Default, PartialEq)]
+struct BatchEntry {
+    url: String,
+    rev: Option<String>,
+    pname: Option<String>,
+    builder: Option<String>,
+    output: Option<PathBuf>,
+}
+
+pub async fn run_batch(opts: Opts, batch: &Path, cfg: Config, clients: Clients) -> Result<()> {
+    let content = if batch == Path::new("-") {
+        io::read_to_string(stdin()).context("failed to read urls from stdin")?
+    } else {
+        read_to_string(batch).context("failed to read batch file")?
+    };
+    let entries = parse_entries(&content)?;
+
+    let cfg = Rc::new(cfg);
+    let clients = Rc::new(Mutex::new(clients));
+    let jobs = Rc::new(Semaphore::new(opts.jobs.max(1)));
+
+    let results = LocalSet::new()
+        .run_until(async move {
+            let mut tasks = JoinSet::new();
+            for (i, entry) in entries.into_iter().enumerate() {
+                let opts = entry.opts(opts.clone())?;
+                let (cfg, clients, jobs) = (cfg.clone(), clients.clone(), jobs.clone());
+                tasks.spawn_local(async move {
+                    let _permit = jobs.acquire().await?;
+                    info!("initializing {}", entry.url);
+                    let res = run(opts, &cfg, &clients, headless_batch()).await;
+                    if let Err(e) = &res {
+                        error!("{}: {e:?}", entry.url);
+                    }
+                    anyhow::Ok((i, entry.url, res))
+                });
+            }
+
+            let mut results = Vec::new();
+            while let Some(res) = tasks.join_next().await {
+                results.push(res??);
+            }
+            results.sort_unstable_by_key(|(i, ..)| *i);
+            anyhow::Ok(results)
+        })
+        .await?;
+
+    let mut failed = 0;
+    eprintln!("{}", "Summary".bold());
+    for (_, url, res) in results {
+        match res {
+            Ok(()) => eprintln!("  {} {url}", "succeeded".green()),
+            Err(e) => {
+                failed += 1;
+                eprintln!("  {} {url}: {e}", "failed".red());
+            }
+        }
+    }
+
+    if failed != 0 {
+        bail!("failed to initialize {failed} package(s)");
+    }
+    Ok(())
+}
+
+impl BatchEntry {
+    fn opts(&self, mut opts: Opts) -> Result<Opts> {
+        opts.url = Some(self.url.clone());
+        opts.rev = self.rev.clone();
+        opts.pname = self.pname.clone();
+        opts.output = self.output.clone();
+        if let Some(builder) = &self.builder {
+            opts.builder = Some(
+                BuilderFunction::from_str(builder, false)
+                    .map_err(|e| anyhow!("{}: invalid builder: {e}", self.url))?,
+            );
+        }
+        opts.batch = None;
+        opts.headless = true;
+        Ok(opts)
+    }
+}
+
+// each line contains a url followed by optional key=value overrides
+// empty lines and lines starting with # are ignored
+fn parse_entries(content: &str) -> Result<Vec<BatchEntry>> {
+    let mut entries = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut args = shlex::split(line)
+            .with_context(|| format!("line {}: invalid quoting", i + 1))?
+            .into_iter();
+        let Some(url) = args.next() else {
+            continue;
+        };
+
+        let mut entry = BatchEntry {
+            url,
+            ..Default::default()
+        };
+        for arg in args {
+            let Some((key, value)) = arg.split_once('=') else {
+                bail!("line {}: expected key=value, found {arg}", i + 1);
+            };
+            let value = value.to_owned();
+            match key {
+                "rev" => entry.rev = Some(value),
+                "pname" => entry.pname = Some(value),
+                "builder" => entry.builder = Some(value),
+                "output" => entry.output = Some(value.into()),
+                _ => bail!("line {}: unknown override {key}", i + 1),
+            }
+        }
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::{BatchEntry, parse_entries};
+
+    #[test]
+    fn basic() {
+        assert_eq!(
+            parse_entries(indoc! {"
+                # tools
+                https://github.com/junegunn/fzf
+
+                https://github.com/nix-community/nurl rev=v0.3.13 pname=nurl builder=buildRustPackage
+                https://github.com/nix-community/nix-init 'output=pkgs/nix init/'
+            "})
+            .unwrap(),
+            [
+                BatchEntry {
+                    url: "https://github.com/junegunn/fzf".into(),
+                    ..Default::default()
+                },
+                BatchEntry {
+                    url: "https://github.com/nix-community/nurl".into(),
+                    rev: Some("v0.3.13".into()),
+                    pname: Some("nurl".into()),
+                    builder: Some("buildRustPackage".into()),
+                    output: None,
+                },
+                BatchEntry {
+                    url: "https://github.com/nix-community/nix-init".into(),
+                    output: Some("pkgs/nix init/".into()),
+                    ..Default::default()
+                },
+            ],
+        );
+
+        assert!(parse_entries("https://github.com/junegunn/fzf rev").is_err());
+        assert!(parse_entries("https://github.com/junegunn/fzf foo=bar").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, absolute},
+    process::Output,
+};
+
+use anyhow::Result;
+use heck::ToKebabCase;
+use regex::Regex;
+use tokio::process::Command;
+use tracing::{error, info, warn};
+
+use crate::{
+    cmd::NIX, codegen::Builder, inputs::AllInputs, lang::pkg_config::pkg_config_input,
+    macros::input_macros, utils::ResultExt,
+};
+
+pub const MAX_BUILD_ATTEMPTS: usize = 3;
+
+// fixes collected from failed builds, applied to the next generated package
+#[derive(Default)]
+pub struct BuildFixes {
+    hashes: Vec<(String, String)>,
+    pub inputs: AllInputs,
+    pub python_deps: BTreeSet<String>,
+    // --update only writes the updated attributes to the existing package,
+    // so missing inputs have to be added by hand
+    hashes_only: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct BuildFailure {
+    hashes: Vec<(String, String)>,
+    pkg_config: BTreeSet<String>,
+    python_modules: BTreeSet<String>,
+}
+
+impl BuildFixes {
+    pub fn new(update: bool) -> Self {
+        Self {
+            hashes_only: update,
+            ..Default::default()
+        }
+    }
+
+    pub fn apply_hashes(&self, mut out: String) -> String {
+        for (specified, got) in &self.hashes {
+            // the source is built before anything that depends on it,
+            // so the first occurrence is the one that failed
+            out = out.replacen(specified, got, 1);
+        }
+        out
+    }
+
+    // returns false if nothing new can be fixed
+    fn merge(&mut self, failure: BuildFailure) -> bool {
+        let mut changed = false;
+
+        for (specified, got) in failure.hashes {
+            if !self.hashes.iter().any(|(x, _)| x == &specified) {
+                info!("fixing hash mismatch: {specified} -> {got}");
+                self.hashes.push((specified, got));
+                changed = true;
+            }
+        }
+
+        if self.hashes_only {
+            for name in failure.pkg_config {
+                warn!(
+                    "{} is needed for pkg-config module {name}, add it to the package",
+                    pkg_config_input(&name),
+                );
+            }
+            for module in failure.python_modules {
+                warn!(
+                    "{} is needed for python module {module}, add it to the package",
+                    module.to_kebab_case(),
+                );
+            }
+            return changed;
+        }
+
+        let inputs = &mut self.inputs;
+        for name in failure.pkg_config {
+            input_macros!(inputs, format_args!("pkg-config module {name} not found"));
+            let input = pkg_config_input(&name);
+            if !inputs.build_inputs.always.contains(&input) {
+                info!("adding {input} for pkg-config module {name}");
+                native_build!("pkg-config");
+                build!(input);
+                changed = true;
+            }
+        }
+
+        for module in failure.python_modules {
+            let dep = module.to_kebab_case();
+            if self.python_deps.insert(dep.clone()) {
+                info!("adding {dep} for python module {module}");
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
+
+// builds the package and collects fixes for the next attempt
+// returns true if the package needs to be regenerated
+pub async fn build_package(
+    fixes: &mut BuildFixes,
+    nixpkgs: &str,
+    path: &Path,
+    builder: &impl Builder,
+) -> Result<bool> {
+    let mut cmd = Command::new(NIX);
+    cmd.arg("build")
+        .arg("--extra-experimental-features")
+        .arg("nix-command")
+        .arg("--impure")
+        .arg("--no-link")
+        .arg("--print-build-logs")
+        // passed as a string, since not every path can be written as a nix path literal
+        .arg("--argstr")
+        .arg("path")
+        .arg(absolute(path)?)
+        .arg("--expr")
+        .arg(format!(
+            "{{path}}:(import({nixpkgs}){{}}).{}(/.+path){{}}",
+            builder.call_package(),
+        ));
+
+    info!("{cmd:?}");
+    let Output { stderr, status, .. } = cmd.output().await?;
+    if status.success() {
+        info!("{} built successfully", path.display());
+        return Ok(false);
+    }
+
+    let log = String::from_utf8_lossy(&stderr);
+    if fixes.merge(parse_build_log(&log)) {
+        return Ok(true);
+    }
+
+    let lines: Vec<_> = log.lines().collect();
+    error!(
+        "{} failed to build\n{}",
+        path.display(),
+        lines[lines.len().saturating_sub(25) ..].join("\n"),
+    );
+    Ok(false)
+}
+
+fn parse_build_log(log: &str) -> BuildFailure {
+    let mut failure = BuildFailure::default();
+
+    let mut lines = log.lines();
+    while let Some(line) = lines.next() {
+        if let Some(specified) = line.trim_start().strip_prefix("specified:")
+            && let Some(got) = lines
+                .next()
+                .and_then(|line| line.trim_start().strip_prefix("got:"))
+        {
+            failure
+                .hashes
+                .push((specified.trim().into(), got.trim().into()));
+        }
+    }
+
+    let patterns = [
+        // pkg-config
+        r"No package '([^']+)' found",
+        // pkgconf
+        r"Package '([^']+)', required by '[^']*', not found",
+        r"Package ([^\s']+) was not found in the pkg-config search path",
+    ];
+    for re in patterns
+        .into_iter()
+        .filter_map(|re| Regex::new(re).ok_inspect(|e| error!("{e}")))
+    {
+        for caps in re.captures_iter(log) {
+            failure.pkg_config.insert(caps[1].into());
+        }
+    }
+
+    let patterns = [
+        // pythonImportsCheck
+        r"No module named '([^'.]+)",
+        // pythonRuntimeDepsCheck
+        r"(?m)^(?:\S+>)?\s+- (\S+) not installed",
+    ];
+    for re in patterns
+        .into_iter()
+        .filter_map(|re| Regex::new(re).ok_inspect(|e| error!("{e}")))
+    {
+        for caps in re.captures_iter(log) {
+            failure.python_modules.insert(caps[1].into());
+        }
+    }
+
+    failure
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::{BuildFailure, BuildFixes, parse_build_log};
+
+    #[test]
+    fn hash_mismatch() {
+        assert_eq!(
+            parse_build_log(indoc! {"
+                error: hash mismatch in fixed-output derivation '/nix/store/xxx-foo-vendor.drv':
+                         specified: sha256-AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=
+                            got:    sha256-Oe/VHHe8d7BKyvmqBjvv4yxLiQUvcz+6Ei7kV8VRFRk=
+            "}),
+            BuildFailure {
+                hashes: vec![(
+                    "sha256-AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".into(),
+                    "sha256-Oe/VHHe8d7BKyvmqBjvv4yxLiQUvcz+6Ei7kV8VRFRk=".into(),
+                )],
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn missing_inputs() {
+        let failure = parse_build_log(indoc! {"
+            foo> Package openssl was not found in the pkg-config search path.
+            foo> No package 'glib-2.0' found
+            foo> Package 'libudev', required by 'virtual:world', not found
+            foo> ModuleNotFoundError: No module named 'requests'
+            foo> Checking runtime dependencies for foo-0.1.0-py3-none-any.whl
+            foo>   - click not installed
+        "});
+        assert_eq!(
+            failure.pkg_config.into_iter().collect::<Vec<_>>(),
+            ["glib-2.0", "libudev", "openssl"],
+        );
+        assert_eq!(
+            failure.python_modules.into_iter().collect::<Vec<_>>(),
+            ["click", "requests"],
+        );
+    }
+
+    #[test]
+    fn hashes_only() {
+        let failure = || BuildFailure {
+            pkg_config: ["openssl".into()].into(),
+            python_modules: ["requests".into()].into(),
+            ..Default::default()
+        };
+
+        let mut fixes = BuildFixes::new(false);
+        assert!(fixes.merge(failure()));
+        assert!(fixes.inputs.build_inputs.always.contains("openssl"));
+        assert!(fixes.python_deps.contains("requests"));
+
+        let mut fixes = BuildFixes::new(true);
+        assert!(!fixes.merge(failure()));
+        assert!(fixes.inputs.build_inputs.always.is_empty());
+        assert!(fixes.python_deps.is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::PathBuf};
 
 use anyhow::{Context, Result};
 use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
@@ -9,16 +9,23 @@ use tokio::process::Command;
 use tracing::warn;
 use xdg::BaseDirectories;
 
-use crate::utils::{CommandExt, ResultExt};
+use crate::{
+    inputs::AllInputs,
+    macros::input_macros,
+    utils::{CommandExt, ResultExt},
+};
 
 #[derive(Default, Deserialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct Config {
     pub commit: bool,
+    pub download_crates: bool,
+    pub explain: bool,
     pub maintainers: Vec<String>,
     pub nixpkgs: Option<String>,
     pub access_tokens: AccessTokens,
     pub format: Option<Format>,
+    pub mappings: Mappings,
 }
 
 #[derive(Default, Deserialize)]
@@ -30,6 +37,42 @@ pub struct Format {
     pub command: Vec<String>,
 }
 
+// extra inputs for dependencies, applied on top of the built-in ones
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mappings {
+    // crate names
+    pub rust: FxHashMap<String, Mapping>,
+    // go module paths
+    pub go: FxHashMap<String, Mapping>,
+    // python build requirements, replaces the inferred input
+    pub python: FxHashMap<String, Mapping>,
+    // npm package names
+    pub npm: FxHashMap<String, Mapping>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Mapping {
+    native_build_inputs: Vec<String>,
+    build_inputs: Vec<String>,
+    // values are nix expressions
+    env: BTreeMap<String, String>,
+    darwin: PlatformMapping,
+    aarch64_darwin: PlatformMapping,
+    x86_64_darwin: PlatformMapping,
+    linux: PlatformMapping,
+    aarch64_linux: PlatformMapping,
+    x86_64_linux: PlatformMapping,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct PlatformMapping {
+    native_build_inputs: Vec<String>,
+    build_inputs: Vec<String>,
+}
+
 impl AccessTokens {
     pub async fn insert_header(&mut self, headers: &mut HeaderMap, host: &str) {
         let value = match self.0.get(host) {
@@ -82,6 +125,37 @@ enum AccessToken {
     File { file: PathBuf },
 }
 
+impl Mapping {
+    pub fn apply(&self, inputs: &mut AllInputs, name: &str) {
+        input_macros!(inputs, name);
+
+        macro_rules! platforms {
+            ($($sys:ident)+) => {
+                $(
+                    for input in &self.$sys.native_build_inputs {
+                        native_build!(input; $sys);
+                    }
+                    for input in &self.$sys.build_inputs {
+                        build!(input; $sys);
+                    }
+                )+
+            };
+        }
+
+        for input in &self.native_build_inputs {
+            native_build!(input);
+        }
+        for input in &self.build_inputs {
+            build!(input);
+        }
+        platforms!(darwin aarch64_darwin x86_64_darwin linux aarch64_linux x86_64_linux);
+
+        for (key, value) in &self.env {
+            environ!(key.clone(), value.clone());
+        }
+    }
+}
+
 pub fn load_config(cfg: Option<PathBuf>) -> Result<Config> {
     Ok(cfg
         .or_else(|| BaseDirectories::with_prefix("nix-init").find_config_file("config.toml"))
@@ -94,3 +168,44 @@ pub fn load_config(cfg: Option<PathBuf>) -> Result<Config> {
         .transpose()?
         .unwrap_or_default())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::inputs::AllInputs;
+
+    #[test]
+    fn mappings() {
+        let cfg: Config = toml::from_str(
+            r#"
+                [mappings.rust.foo-sys]
+                native-build-inputs = ["pkg-config"]
+                build-inputs = ["libfoo"]
+                env.FOO_NO_VENDOR = "true"
+                linux.build-inputs = ["libbar"]
+                aarch64-darwin.native-build-inputs = ["baz"]
+            "#,
+        )
+        .unwrap();
+
+        let mut inputs = AllInputs::default();
+        cfg.mappings.rust["foo-sys"].apply(&mut inputs, "foo-sys");
+
+        assert_eq!(
+            Vec::from_iter(&inputs.native_build_inputs.always),
+            ["pkg-config"],
+        );
+        assert_eq!(
+            Vec::from_iter(&inputs.native_build_inputs.aarch64_darwin),
+            ["baz"],
+        );
+        assert_eq!(Vec::from_iter(&inputs.build_inputs.always), ["libfoo"]);
+        assert_eq!(Vec::from_iter(&inputs.build_inputs.linux), ["libbar"]);
+        assert_eq!(inputs.env["FOO_NO_VENDOR"].0, "true");
+        assert_eq!(Vec::from_iter(&inputs.reasons["libbar"]), ["foo-sys"]);
+        assert_eq!(
+            Vec::from_iter(&inputs.reasons["FOO_NO_VENDOR"]),
+            ["foo-sys"]
+        );
+    }
+}
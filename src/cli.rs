@@ -4,7 +4,7 @@ use clap::{Parser, ValueEnum};
 
 /// Generate Nix packages with hash prefetching, license detection, and more
 /// https://github.com/nix-community/nix-init
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 #[command(verbatim_doc_comment)]
 pub struct Opts {
     /// The path or directory to output the generated file to
@@ -40,16 +40,81 @@ pub struct Opts {
     #[arg(long)]
     pub cargo_vendor: Option<CargoVendor>,
 
+    /// Specify the node package manager instead of detecting it from the lockfiles
+    ///
+    /// also makes --builder stdenv.mkDerivation use its fetcher and hooks
+    #[arg(long)]
+    pub node_package_manager: Option<PackageManager>,
+
+    /// Enable the given cargo features instead of prompting for them
+    ///
+    /// the default features are still enabled unless --no-default-features is specified
+    #[arg(short = 'F', long, value_delimiter = ',')]
+    pub features: Option<Vec<String>>,
+
+    /// Disable the default cargo features instead of prompting for them
+    #[arg(long)]
+    pub no_default_features: bool,
+
+    /// Download crates that are not available locally to find the native libraries they link to
+    #[arg(long, num_args=0..=1, require_equals = true, default_missing_value = "true")]
+    pub download_crates: Option<bool>,
+
     /// Always overwrite files
     ///
     /// use --overwrite=false to never overwrite files
     #[arg(short = 'y', long, num_args=0..=1, require_equals = true, default_missing_value = "true")]
     pub overwrite: Option<bool>,
 
+    /// Update the existing package at OUTPUT instead of creating a new one
+    ///
+    /// only the version, source hash, and dependency hashes are regenerated,
+    /// other attributes are left untouched
+    #[arg(short = 'U', long)]
+    pub update: bool,
+
+    /// Build the generated package and retry with fixes for common failures
+    ///
+    /// fixes hash mismatches, missing pkg-config modules, and missing python modules
+    #[arg(short, long)]
+    pub build: bool,
+
     /// Don't prompt for anything (requires --url)
     #[arg(long)]
     pub headless: bool,
 
+    /// Read answers to the prompts from a TOML or JSON file
+    ///
+    /// keys: url, rev, fetch-submodules, version, pname, builder, cargo-members,
+    /// cargo-features, main-program, output, overwrite
+    /// {n}missing answers are prompted for, or are an error with --headless
+    #[arg(short, long, value_name = "FILE")]
+    pub answers: Option<PathBuf>,
+
+    /// Initialize packages from a file with one URL per line, use - to read from stdin
+    ///
+    /// each URL can be followed by overrides for that package, e.g.
+    /// {n}  https://github.com/owner/repo rev=v1.0.0 pname=foo builder=buildGoModule output=foo/
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["output", "url"])]
+    pub batch: Option<PathBuf>,
+
+    /// Maximum number of packages to initialize at the same time in batch mode
+    #[arg(short, long, default_value_t = 4)]
+    pub jobs: usize,
+
+    /// Explain why each input was added with a comment next to it
+    ///
+    /// use --explain=false to never add the comments
+    #[arg(short, long, num_args=0..=1, require_equals = true, default_missing_value = "true")]
+    pub explain: Option<bool>,
+
+    /// Print a report of everything nix-init inferred to stdout
+    ///
+    /// includes the source, layout, builder, license scores, hashes,
+    /// and the inputs along with the dependencies that caused them to be added
+    #[arg(long, value_name = "FORMAT")]
+    pub report: Option<ReportFormat>,
+
     /// Path to nixpkgs (in nix)
     ///
     /// Examples:
@@ -84,6 +149,11 @@ pub enum BuilderFunction {
     MkDerivationNoCC,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    Json,
+}
+
 #[derive(Clone, Copy, ValueEnum)]
 #[clap(rename_all = "camelCase")]
 #[cfg_attr(
@@ -95,3 +165,18 @@ pub enum CargoVendor {
     FetchCargoVendor,
     ImportCargoLock,
 }
+
+// package managers other than npm, identified by their lockfiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(
+    not_build,
+    derive(parse_display::Display, serde::Serialize),
+    display(style = "kebab-case"),
+    serde(rename_all = "kebab-case")
+)]
+pub enum PackageManager {
+    Bun,
+    Pnpm,
+    Yarn,
+    YarnBerry,
+}
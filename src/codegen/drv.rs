@@ -1,4 +1,7 @@
-use std::fmt::{self, Display, Formatter};
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+};
 
 use crate::{cli::CargoVendor, codegen::Builder};
 
@@ -22,11 +25,11 @@ impl MkDerivation {
 }
 
 impl Builder for MkDerivation {
-    fn function(&self) -> &'static str {
+    fn function(&self) -> Cow<'static, str> {
         if self.cc {
-            "stdenv.mkDerivation"
+            "stdenv.mkDerivation".into()
         } else {
-            "stdenvNoCC.mkDerivation"
+            "stdenvNoCC.mkDerivation".into()
         }
     }
 
@@ -1,47 +1,100 @@
-use std::fmt::Write as _;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter, Write as _},
+    path::Path,
+};
 
 use anyhow::Result;
-use parse_display::Display;
 use tracing::warn;
 
 use crate::{
     codegen::{Builder, Codegen},
-    lang::go::{load_go_dependencies, write_ldflags},
-    utils::{FAKE_HASH, ResultExt, fod_hash},
+    lang::go::{
+        go_binaries, go_builder, go_main_packages, goreleaser_build, goreleaser_builds,
+        load_cgo_dependencies, load_go_dependencies, load_goreleaser_env, scan_cgo,
+        write_build_flags,
+    },
+    utils::{FAKE_HASH, ResultExt},
 };
 
-#[derive(Clone, Copy, Display)]
-#[display("buildGoModule")]
-pub struct BuildGoModule;
+#[derive(Clone, Copy, Default)]
+pub struct BuildGoModule {
+    // minor version of go for the versioned builders, e.g. buildGo124Module
+    go: Option<u32>,
+}
+
+impl BuildGoModule {
+    // switches to a versioned builder if go.mod requires a newer go than the default one
+    pub async fn detect_toolchain(self, src_dir: &Path, nixpkgs: &str) -> Self {
+        Self {
+            go: go_builder(src_dir, nixpkgs).await,
+        }
+    }
+}
 
 impl Builder for BuildGoModule {
-    fn function(&self) -> &'static str {
-        "buildGoModule"
+    fn function(&self) -> Cow<'static, str> {
+        match self.go {
+            Some(minor) => format!("buildGo1{minor}Module").into(),
+            None => "buildGoModule".into(),
+        }
     }
 
     async fn after_src(&self, cg: &mut Codegen<'_>) -> Result<String> {
         let mut out = String::new();
-        let go_sum = std::fs::File::open(cg.src_dir.join("go.sum")).ok_inspect(|e| warn!("{e}"));
+        let go_sum =
+            std::fs::File::open(cg.project_dir.join("go.sum")).ok_inspect(|e| warn!("{e}"));
 
         if let Some(go_sum) = &go_sum {
-            load_go_dependencies(&mut cg.inputs, go_sum);
+            load_go_dependencies(&mut cg.inputs, cg.mappings, go_sum);
         }
 
-        let hash = if cg.src_dir.join("vendor").is_dir()
-            || go_sum
-                .and_then(|go_sum| go_sum.metadata().ok_inspect(|e| warn!("{e}")))
-                .is_none_or(|metadata| metadata.len() == 0)
+        // the build matching the main program decides the flags and environment
+        if cg.main_program.is_none() && goreleaser_builds(cg.project_dir).len() > 1 {
+            let programs = self.main_programs(cg);
+            cg.select_main_program(programs)?;
+        }
+        if let Some(build) = goreleaser_build(cg.project_dir, cg.main_program.as_deref()) {
+            load_goreleaser_env(&mut cg.inputs, &build);
+        }
+
+        let has_deps = go_sum
+            .and_then(|go_sum| go_sum.metadata().ok_inspect(|e| warn!("{e}")))
+            .is_some_and(|metadata| metadata.len() != 0);
+        let vendored = cg.project_dir.join("vendor").is_dir();
+
+        let cgo = scan_cgo(cg.project_dir);
+        load_cgo_dependencies(&mut cg.inputs, cg.nixpkgs, &cgo).await;
+        // cgo can only be ruled out when the sources of all the dependencies were scanned,
+        // dependencies known to need native libraries use cgo even if the sources don't
+        if !cgo.enabled
+            && (vendored || !has_deps)
+            && cg.inputs.build_inputs.is_empty()
+            && !cg.inputs.env.keys().any(|name| name.starts_with("CGO_"))
         {
+            cg.inputs.add_reason("CGO_ENABLED", "no cgo usage found");
+            cg.inputs
+                .env
+                .insert("CGO_ENABLED".into(), ("0".into(), Vec::new()));
+        }
+
+        let hash = if vendored || !has_deps {
             "null".into()
-        } else if let Some(hash) = fod_hash(format!(
-            r#"(import({}){{}}).buildGoModule{{pname={:?};version={:?};src={};vendorHash="{FAKE_HASH}";}}"#,
-            cg.nixpkgs, cg.pname, cg.version, cg.src,
-        ))
-        .await
-        {
-            format!(r#""{hash}""#)
         } else {
-            format!(r#""{FAKE_HASH}""#)
+            let expr = format!(
+                r#"(import({}){{}}).{}{{pname={:?};version={:?};src={};modRoot={:?};vendorHash="{FAKE_HASH}";}}"#,
+                cg.nixpkgs,
+                self.function(),
+                cg.pname,
+                cg.version,
+                cg.src,
+                cg.root.unwrap_or("."),
+            );
+            let hash = cg
+                .prefetch_hash("vendorHash", expr)
+                .await
+                .unwrap_or_else(|| FAKE_HASH.into());
+            format!(r#""{hash}""#)
         };
 
         writeln!(out, "  vendorHash = {hash};\n")?;
@@ -50,7 +103,32 @@ impl Builder for BuildGoModule {
 
     fn after_inputs(&self, cg: &mut Codegen<'_>) -> Result<String> {
         let mut out = String::new();
-        write_ldflags(&mut out, cg.src_dir)?;
+
+        if let Some(pkgs) = go_main_packages(cg.project_dir).sub_packages() {
+            write!(out, "  subPackages = [")?;
+            for pkg in pkgs {
+                write!(out, " {pkg:?}")?;
+            }
+            writeln!(out, " ];\n")?;
+        }
+
+        let build = goreleaser_build(cg.project_dir, cg.main_program.as_deref());
+        write_build_flags(&mut out, build.as_ref())?;
         Ok(out)
     }
+
+    fn write_root(&self, out: &mut String, root: &str) -> Result<()> {
+        writeln!(out, "  modRoot = {root:?};\n")?;
+        Ok(())
+    }
+
+    fn main_programs(&self, cg: &Codegen<'_>) -> Vec<String> {
+        go_binaries(cg.project_dir, &go_main_packages(cg.project_dir).selected)
+    }
+}
+
+impl Display for BuildGoModule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function())
+    }
 }
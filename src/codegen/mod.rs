@@ -5,12 +5,14 @@ pub mod python;
 pub mod rust;
 
 use std::{
+    borrow::Cow,
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet},
-    fmt::Write as _,
-    fs::read_dir,
+    fmt::{self, Write as _},
+    fs::{read_dir, read_to_string},
+    mem,
     os::unix::ffi::OsStrExt,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 use anyhow::Result;
@@ -21,22 +23,30 @@ use expand::expand;
 use indoc::writedoc;
 use itertools::Itertools;
 use parse_display::Display;
+use reqwest::Client;
+use serde::Serialize;
 use tracing::warn;
 
 use crate::{
-    cli::CargoVendor,
+    cfg::Mappings,
+    cli::{CargoVendor, PackageManager},
     codegen::{
-        drv::MkDerivation, go::BuildGoModule, npm::BuildNpmPackage, python::BuildPythonPackage,
+        drv::MkDerivation,
+        go::BuildGoModule,
+        npm::{BuildNpmPackage, MkNodeDerivation},
+        python::BuildPythonPackage,
         rust::BuildRustPackage,
     },
-    frontend::FrontendDispatch,
-    inputs::{AllInputs, write_all_lambda_inputs, write_inputs, write_lambda_input},
+    frontend::{Frontend, FrontendDispatch},
+    inputs::{AllInputs, Comments, write_all_lambda_inputs, write_inputs, write_lambda_input},
     lang::{
         python::PythonDependencies,
-        rust::{cargo_deps_hash, load_cargo_lock, write_cargo_lock},
+        rust::{CargoFeatures, CargoSelection, cargo_deps_hash, load_cargo_lock, write_cargo_lock},
     },
     license::{LICENSE_STORE, load_license},
-    utils::ResultExt,
+    macros::input_macros,
+    utils::{FAKE_HASH, ResultExt, fod_hash},
+    validate::validate_inputs,
 };
 
 #[enum_dispatch(Builder)]
@@ -48,25 +58,62 @@ pub enum BuilderDispatch {
     BuildPythonPackage(BuildPythonPackage),
     BuildRustPackage(BuildRustPackage),
     MkDerivation(MkDerivation),
+    MkNodeDerivation(MkNodeDerivation),
+}
+
+// a builder for the project in a subdirectory of the source, or at the top level
+#[derive(Clone)]
+pub struct BuilderCandidate {
+    pub builder: BuilderDispatch,
+    pub root: Option<String>,
+}
+
+impl fmt::Display for BuilderCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.root {
+            Some(root) => write!(f, "{} ({root})", self.builder),
+            None => write!(f, "{}", self.builder),
+        }
+    }
 }
 
 pub struct Codegen<'a> {
+    // features to build, all of them are considered when unspecified
+    pub cargo_features: Option<CargoFeatures>,
+    // selected members of the cargo workspace, empty for the default members
+    // None until they are selected, so retries of --build don't prompt again
+    pub cargo_members: Option<Vec<String>>,
+    // set with --download-crates, to download crates that are not available locally
+    pub crates_io: Option<Client>,
     pub description: String,
+    pub explain: bool,
+    pub extra_python_deps: &'a BTreeSet<String>,
     pub fetcher_input: String,
     pub file_url_prefix: Option<String>,
     pub frontend: &'a mut FrontendDispatch,
+    // hashes of fixed-output derivations, keyed by the attribute they are assigned to
+    pub hashes: BTreeMap<&'static str, String>,
     pub inputs: AllInputs,
     pub layout: SourceLayout,
     pub licenses: BTreeMap<&'static str, f32>,
+    // set when the builder needs to know the main program before the metadata is written
+    pub main_program: Option<String>,
     pub maintainers: &'a [String],
+    pub mappings: &'a Mappings,
+    // inputs and python dependencies that were not found in nixpkgs
+    pub missing_inputs: BTreeSet<String>,
     pub nix_update_script: bool,
     pub nixpkgs: &'a str,
     pub out: String,
     pub out_dir: Option<&'a Path>,
     pub overwrite: Option<bool>,
     pub pname: &'a str,
+    // the directory of the project, either `src_dir` or `root` in it
+    pub project_dir: &'a Path,
     pub python_deps: PythonDependencies,
     pub releases_page: Option<String>,
+    // subdirectory of the source containing the project
+    pub root: Option<&'a str>,
     pub src: &'a str,
     pub src_dir: &'a Path,
     pub src_expr: &'a str,
@@ -74,6 +121,7 @@ pub struct Codegen<'a> {
     pub version: &'a str,
 }
 
+#[derive(Clone, Copy, Serialize)]
 pub struct SourceLayout {
     pub has_cargo: bool,
     pub has_cargo_lock: bool,
@@ -84,6 +132,7 @@ pub struct SourceLayout {
     pub has_npm_lock: bool,
     pub has_python: bool,
     pub has_zig: bool,
+    pub package_manager: Option<PackageManager>,
 }
 
 enum CargoDeps {
@@ -96,7 +145,7 @@ enum CargoDeps {
 
 #[enum_dispatch]
 pub trait Builder {
-    fn function(&self) -> &'static str;
+    fn function(&self) -> Cow<'static, str>;
 
     fn after_version(&self, _: &mut Codegen<'_>) -> Result<String> {
         Ok(String::new())
@@ -130,10 +179,21 @@ pub trait Builder {
         Ok(String::new())
     }
 
+    // points the builder to the project in a subdirectory of the source
+    fn write_root(&self, out: &mut String, root: &str) -> Result<()> {
+        writeln!(out, "  sourceRoot = \"${{finalAttrs.src.name}}/{root}\";\n")?;
+        Ok(())
+    }
+
     fn has_main_program(&self) -> bool {
         true
     }
 
+    // executables declared in the manifest, mainProgram falls back to pname without any
+    fn main_programs(&self, _: &Codegen<'_>) -> Vec<String> {
+        Vec::new()
+    }
+
     fn explicit_platforms(&self) -> bool {
         false
     }
@@ -141,14 +201,18 @@ pub trait Builder {
     fn allow_by_name(&self) -> bool {
         true
     }
+
+    fn call_package(&self) -> &'static str {
+        "callPackage"
+    }
 }
 
 impl Codegen<'_> {
-    pub async fn generate(mut self, builder: impl Builder) -> Result<String> {
+    pub async fn generate(&mut self, builder: &impl Builder) -> Result<String> {
         let function = builder.function();
         let builder_input = function
             .split_once('.')
-            .map_or(function, |(input, _)| input);
+            .map_or(&*function, |(input, _)| input);
 
         writedoc!(
             self.out,
@@ -162,44 +226,41 @@ impl Codegen<'_> {
         )?;
 
         if builder.infer_setup_hooks() {
+            let inputs = &mut self.inputs;
             if self.layout.has_cmake {
-                self.inputs
-                    .native_build_inputs
-                    .always
-                    .insert("cmake".into());
+                input_macros!(inputs, "CMakeLists.txt");
+                native_build!("cmake");
             }
             if self.layout.has_meson {
-                self.inputs
-                    .native_build_inputs
-                    .always
-                    .extend(["meson".into(), "ninja".into()]);
+                input_macros!(inputs, "meson.build");
+                native_build!("meson", "ninja");
             }
             if self.layout.has_zig {
-                self.inputs.native_build_inputs.always.insert("zig".into());
+                input_macros!(inputs, "build.zig");
+                native_build!("zig");
             }
         }
 
-        let after_version = builder.after_version(&mut self)?;
-        let mut after_src = builder.after_src(&mut self).await?;
+        let after_version = builder.after_version(self)?;
+        let mut after_src = builder.after_src(self).await?;
         let cargo_deps = builder.cargo_deps();
         if let Some(vendor) = cargo_deps {
-            self.inputs.native_build_inputs.always.extend([
-                "cargo".into(),
-                "rustPlatform.cargoSetupHook".into(),
-                "rustc".into(),
-            ]);
-            match prepare_cargo_deps(&mut self, vendor).await? {
+            let inputs = &mut self.inputs;
+            input_macros!(inputs, "Cargo.toml");
+            native_build!("cargo", "rustPlatform.cargoSetupHook", "rustc");
+            match prepare_cargo_deps(self, vendor, "cargoDeps").await? {
                 CargoDeps::Hash(hash) => {
                     write!(after_src, "  ")?;
                     writedoc! {
                         after_src,
                         r#"
                             cargoDeps = rustPlatform.fetchCargoVendor {{
-                                inherit (finalAttrs) pname version src;
+                                inherit (finalAttrs) pname version src{source_root};
                                 hash = "{hash}";
                               }};
 
                         "#,
+                        source_root = if self.root.is_some() { " sourceRoot" } else { "" },
                     }?;
                 }
                 CargoDeps::Lock {
@@ -211,7 +272,8 @@ impl Codegen<'_> {
                 }
             }
         }
-        let after_inputs = builder.after_inputs(&mut self)?;
+        validate_inputs(self, builder.call_package().strip_suffix(".callPackage")).await;
+        let after_inputs = builder.after_inputs(self)?;
 
         let mut written = BTreeSet::from([builder_input.into()]);
         if cargo_deps.is_some() {
@@ -219,7 +281,7 @@ impl Codegen<'_> {
         }
         let (native_build_inputs, build_inputs) =
             write_all_lambda_inputs(&mut self.out, &self.inputs, &mut written)?;
-        for input in builder.extra_lambda_inputs(&self) {
+        for input in builder.extra_lambda_inputs(self) {
             write_lambda_input(&mut self.out, &mut written, &input)?;
         }
         if self.nix_update_script {
@@ -244,24 +306,39 @@ impl Codegen<'_> {
             writeln!(self.out, "  strictDeps = true;")?;
         }
         writeln!(self.out, "\n  src = {};\n", self.src_expr)?;
+        if let Some(root) = self.root {
+            builder.write_root(&mut self.out, root)?;
+        }
 
         write!(self.out, "{after_src}")?;
+        let comments = Comments {
+            reasons: self.explain.then_some(&self.inputs.reasons),
+            missing: &self.missing_inputs,
+        };
         if native_build_inputs {
             write_inputs(
                 &mut self.out,
                 &self.inputs.native_build_inputs,
                 builder.native_build_inputs_attr(),
+                &comments,
             )?;
         }
         if build_inputs {
-            write_inputs(&mut self.out, &self.inputs.build_inputs, "buildInputs")?;
+            write_inputs(
+                &mut self.out,
+                &self.inputs.build_inputs,
+                "buildInputs",
+                &comments,
+            )?;
         }
         write!(self.out, "{after_inputs}")?;
 
         if !self.inputs.env.is_empty() {
             writeln!(self.out, "  env = {{")?;
-            for (k, (v, _)) in std::mem::take(&mut self.inputs.env) {
-                writeln!(self.out, "    {k} = {v};")?;
+            for (k, (v, _)) in &self.inputs.env {
+                write!(self.out, "    {k} = {v};")?;
+                comments.write(&mut self.out, k)?;
+                writeln!(self.out)?;
             }
             writeln!(self.out, "  }};\n")?;
         }
@@ -273,10 +350,10 @@ impl Codegen<'_> {
             )?;
         }
 
-        self.write_meta(&builder)?;
+        self.write_meta(builder)?;
         writeln!(self.out, "}})")?;
 
-        Ok(self.out)
+        Ok(mem::take(&mut self.out))
     }
 
     fn write_meta(&mut self, builder: &impl Builder) -> Result<()> {
@@ -313,7 +390,11 @@ impl Codegen<'_> {
         }
 
         if builder.has_main_program() {
-            writeln!(self.out, "    mainProgram = {:?};", self.pname)?;
+            let main_program = match &self.main_program {
+                Some(main_program) => main_program.clone(),
+                None => self.select_main_program(builder.main_programs(self))?,
+            };
+            writeln!(self.out, "    mainProgram = {main_program:?};")?;
         }
 
         if builder.explicit_platforms() {
@@ -324,6 +405,41 @@ impl Codegen<'_> {
         Ok(())
     }
 
+    // prefetches the hash of a fixed-output derivation assigned to `attr`,
+    // reusing the one from a previous attempt of --build
+    pub async fn prefetch_hash(&mut self, attr: &'static str, expr: String) -> Option<String> {
+        if let Some(hash) = self.hashes.get(attr) {
+            return Some(hash.clone());
+        }
+        let hash = fod_hash(expr).await?;
+        self.hashes.insert(attr, hash.clone());
+        Some(hash)
+    }
+
+    // the source of the project, for fixed-output derivations that only depend on its lock files
+    pub fn project_src(&self) -> String {
+        match self.root {
+            Some(root) => format!("{}/{root}", self.src),
+            None => self.src.into(),
+        }
+    }
+
+    pub fn select_main_program(&mut self, mut programs: Vec<String>) -> Result<String> {
+        let main_program = match programs.len() {
+            0 => self.pname.into(),
+            1 => programs.remove(0),
+            _ => {
+                // suggest the program named after the package first
+                if let Some(i) = programs.iter().position(|program| program == self.pname) {
+                    programs[.. i + 1].rotate_right(1);
+                }
+                self.frontend.main_program(programs)?
+            }
+        };
+        self.main_program = Some(main_program.clone());
+        Ok(main_program)
+    }
+
     fn write_changelog(&mut self) -> Result<()> {
         let mut found_changelog = false;
         if let Some(file_url_prefix) = &self.file_url_prefix
@@ -431,28 +547,50 @@ impl Codegen<'_> {
     }
 }
 
-async fn prepare_cargo_deps(cg: &mut Codegen<'_>, vendor: CargoVendor) -> Result<CargoDeps> {
+// `attr` is where the hash of fetchCargoVendor is assigned to
+async fn prepare_cargo_deps(
+    cg: &mut Codegen<'_>,
+    vendor: CargoVendor,
+    attr: &'static str,
+) -> Result<CargoDeps> {
+    let selection = CargoSelection {
+        members: cg.cargo_members.as_deref().unwrap_or_default(),
+        features: cg.cargo_features.as_ref(),
+    };
+    let src = cg.project_src();
     Ok(match vendor {
-        CargoVendor::FetchCargoVendor => CargoDeps::Hash(
-            cargo_deps_hash(
+        CargoVendor::FetchCargoVendor => {
+            let hash = cargo_deps_hash(
                 &mut cg.inputs,
+                cg.mappings,
+                selection,
                 cg.pname,
                 cg.version,
-                cg.src,
-                cg.src_dir,
+                src,
+                cg.project_dir,
                 cg.layout.has_cargo_lock,
                 cg.nixpkgs,
+                cg.hashes.get(attr).cloned(),
+                cg.crates_io.as_ref(),
             )
-            .await,
-        ),
+            .await;
+            if hash != FAKE_HASH {
+                cg.hashes.insert(attr, hash.clone());
+            }
+            CargoDeps::Hash(hash)
+        }
         CargoVendor::ImportCargoLock => {
             let resolve = if let Some(out_dir) = cg.out_dir {
                 load_cargo_lock(
                     cg.frontend,
                     out_dir,
                     &mut cg.inputs,
-                    cg.src_dir,
+                    cg.mappings,
+                    selection,
+                    cg.project_dir,
+                    cg.nixpkgs,
                     cg.overwrite,
+                    cg.crates_io.as_ref(),
                 )
                 .await?
             } else {
@@ -480,6 +618,271 @@ impl SourceLayout {
             has_python: src_dir.join("pyproject.toml").is_file()
                 || src_dir.join("setup.py").is_file(),
             has_zig: src_dir.join("build.zig").is_file(),
+            package_manager: PackageManager::detect(src_dir),
         }
     }
 }
+
+// files marking the root of a project, along with the build system they belong to
+const PROJECT_MARKERS: [(&str, &str); 8] = [
+    ("CMakeLists.txt", "cmake"),
+    ("Cargo.toml", "cargo"),
+    ("build.zig", "zig"),
+    ("go.mod", "go"),
+    ("meson.build", "meson"),
+    ("package.json", "npm"),
+    ("pyproject.toml", "python"),
+    ("setup.py", "python"),
+];
+
+// subdirectories containing a project, e.g. a Go module in a Rust repository
+// parts of a project already found in a parent directory are skipped,
+// such as the members of a cargo workspace or the subdirectories of a meson project
+pub fn project_roots(src_dir: &Path) -> Vec<String> {
+    let mut roots = BTreeSet::new();
+    find_roots(src_dir, Path::new(""), 3, &BTreeSet::new(), &mut roots);
+    roots.into_iter().collect()
+}
+
+fn find_roots(
+    src_dir: &Path,
+    dir: &Path,
+    depth: usize,
+    parents: &BTreeSet<&'static str>,
+    roots: &mut BTreeSet<String>,
+) {
+    let path = src_dir.join(dir);
+    let mut systems: BTreeSet<_> = PROJECT_MARKERS
+        .iter()
+        .filter(|(marker, _)| path.join(marker).is_file())
+        .map(|(_, system)| *system)
+        .collect();
+
+    let is_root = !dir.as_os_str().is_empty();
+    if is_root && !systems.is_subset(parents) {
+        roots.insert(dir.to_string_lossy().into_owned());
+    }
+
+    // modules used by the workspace are roots of their own
+    if !systems.contains("go")
+        && !parents.contains("go")
+        && let Ok(go_work) = read_to_string(path.join("go.work"))
+    {
+        roots.extend(
+            go_work_modules(&go_work)
+                .map(Path::new)
+                // skip modules without a go.mod or outside of the source
+                .filter(|module| {
+                    module
+                        .components()
+                        .all(|component| matches!(component, Component::Normal(_)))
+                        && path.join(module).join("go.mod").is_file()
+                })
+                .map(|module| dir.join(module).to_string_lossy().into_owned()),
+        );
+    }
+
+    let Some(depth) = depth.checked_sub(1) else {
+        return;
+    };
+    let Ok(entries) = read_dir(path) else {
+        return;
+    };
+    systems.extend(parents);
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.starts_with(['.', '_'])
+            || matches!(
+                name,
+                "3rdparty"
+                    | "benches"
+                    | "deps"
+                    | "doc"
+                    | "docs"
+                    | "example"
+                    | "examples"
+                    | "fixtures"
+                    | "node_modules"
+                    | "subprojects"
+                    | "target"
+                    | "test"
+                    | "testdata"
+                    | "tests"
+                    | "third_party"
+                    | "vendor"
+            )
+            || !entry.file_type().is_ok_and(|ty| ty.is_dir())
+        {
+            continue;
+        }
+
+        find_roots(src_dir, &dir.join(name), depth, &systems, roots);
+    }
+}
+
+// the modules in `use` directives, e.g. `use ./cli` or `use ( ./cli ./lib )`
+fn go_work_modules(go_work: &str) -> impl Iterator<Item = &str> {
+    let mut block = false;
+    go_work.lines().filter_map(move |line| {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        let module = if block {
+            if line == ")" {
+                block = false;
+                return None;
+            }
+            line
+        } else {
+            let rest = line.strip_prefix("use")?.trim();
+            if rest == "(" {
+                block = true;
+                return None;
+            }
+            rest
+        };
+        let module = module.trim_matches('"').trim_start_matches("./");
+        (!module.is_empty() && module != ".").then_some(module)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        fs::{create_dir_all, write},
+    };
+
+    use indoc::indoc;
+    use tempfile::tempdir;
+
+    use super::{Codegen, SourceLayout, drv::MkDerivation, project_roots};
+    use crate::{cfg::Mappings, frontend::headless};
+
+    #[tokio::test]
+    async fn root() {
+        let dir = tempdir().unwrap();
+        let src_dir = dir.path();
+        create_dir_all(src_dir.join("cli")).unwrap();
+        let project_dir = src_dir.join("cli");
+
+        let mut frontend = headless();
+        let mut cg = Codegen {
+            cargo_features: None,
+            cargo_members: None,
+            crates_io: None,
+            description: "a tool".into(),
+            explain: false,
+            extra_python_deps: &BTreeSet::new(),
+            fetcher_input: "fetchFromGitHub".into(),
+            file_url_prefix: None,
+            frontend: &mut frontend,
+            hashes: BTreeMap::new(),
+            inputs: Default::default(),
+            layout: SourceLayout::detect(&project_dir),
+            licenses: BTreeMap::new(),
+            main_program: None,
+            maintainers: &[],
+            mappings: &Mappings::default(),
+            missing_inputs: BTreeSet::new(),
+            nix_update_script: false,
+            nixpkgs: "<nixpkgs>",
+            out: String::new(),
+            out_dir: None,
+            overwrite: None,
+            pname: "foo",
+            project_dir: &project_dir,
+            python_deps: Default::default(),
+            releases_page: None,
+            root: Some("cli"),
+            src: "/nix/store/00000000000000000000000000000000-source",
+            src_dir,
+            src_expr: "fetchFromGitHub { }",
+            url: "https://github.com/owner/foo",
+            version: "1.0.0",
+        };
+
+        assert_eq!(
+            cg.generate(&MkDerivation::no_cc()).await.unwrap(),
+            indoc! {r#"
+                {
+                  lib,
+                  stdenvNoCC,
+                  fetchFromGitHub,
+                }:
+
+                stdenvNoCC.mkDerivation (finalAttrs: {
+                  pname = "foo";
+                  version = "1.0.0";
+                  __structuredAttrs = true;
+                  strictDeps = true;
+
+                  src = fetchFromGitHub { };
+
+                  sourceRoot = "${finalAttrs.src.name}/cli";
+
+                  meta = {
+                    description = "A tool";
+                    homepage = "https://github.com/owner/foo";
+                    license = lib.licenses.unfree; # FIXME: nix-init did not find a license
+                    maintainers = with lib.maintainers; [ ];
+                    mainProgram = "foo";
+                    platforms = lib.platforms.all;
+                  };
+                })
+            "#},
+        );
+    }
+
+    #[test]
+    fn roots() {
+        let dir = tempdir().unwrap();
+        let src = dir.path();
+        for dir in [
+            "cli",
+            "tools/gen",
+            "web/node_modules/foo",
+            "rust/crates/foo",
+            "go/lib",
+            "native/src",
+            "examples/demo",
+        ] {
+            create_dir_all(src.join(dir)).unwrap();
+        }
+        write(src.join("cli/go.mod"), "").unwrap();
+        write(src.join("tools/gen/go.mod"), "").unwrap();
+        write(src.join("web/package.json"), "").unwrap();
+        write(src.join("web/node_modules/foo/package.json"), "").unwrap();
+        write(src.join("rust/Cargo.toml"), "").unwrap();
+        write(src.join("rust/crates/foo/Cargo.toml"), "").unwrap();
+        write(src.join("native/meson.build"), "").unwrap();
+        write(src.join("native/src/meson.build"), "").unwrap();
+        write(src.join("examples/demo/go.mod"), "").unwrap();
+        write(src.join("go/lib/go.mod"), "").unwrap();
+        write(
+            src.join("go/go.work"),
+            indoc! {"
+                go 1.24
+
+                use (
+                    ./lib // the library
+                    ./tools
+                    ../outside
+                    /abs/mod
+                )
+            "},
+        )
+        .unwrap();
+
+        assert_eq!(
+            project_roots(src),
+            ["cli", "go/lib", "native", "rust", "tools/gen", "web",],
+        );
+
+        write(src.join("go.mod"), "").unwrap();
+        write(src.join("package.json"), "").unwrap();
+        assert_eq!(project_roots(src), ["native", "rust"]);
+    }
+}
@@ -1,31 +1,53 @@
-use std::fmt::Write as _;
+use std::{borrow::Cow, fmt::Write as _, path::Path};
 
 use anyhow::Result;
+use indoc::writedoc;
 use parse_display::Display;
 
 use crate::{
+    cli::PackageManager,
     codegen::{Builder, Codegen},
-    lang::npm::npm_has_build_script,
-    utils::{FAKE_HASH, fod_hash},
+    lang::npm::{load_npm_dependencies, npm_bins, npm_has_build_script, npm_nodejs_major},
+    macros::input_macros,
+    utils::FAKE_HASH,
 };
 
-#[derive(Clone, Copy, Display)]
+#[derive(Clone, Copy, Default, Display)]
 #[display("buildNpmPackage")]
-pub struct BuildNpmPackage;
+pub struct BuildNpmPackage {
+    // major version of nodejs_XX overriding the default nodejs
+    nodejs: Option<u32>,
+}
+
+impl BuildNpmPackage {
+    // switches to a versioned nodejs if engines.node isn't satisfied by the default one
+    pub async fn detect_nodejs(self, src_dir: &Path, nixpkgs: &str) -> Self {
+        Self {
+            nodejs: npm_nodejs_major(src_dir, nixpkgs).await,
+        }
+    }
+}
 
 impl Builder for BuildNpmPackage {
-    fn function(&self) -> &'static str {
-        "buildNpmPackage"
+    fn function(&self) -> Cow<'static, str> {
+        "buildNpmPackage".into()
     }
 
     async fn after_src(&self, cg: &mut Codegen<'_>) -> Result<String> {
         let mut out = String::new();
+        load_npm_dependencies(&mut cg.inputs, cg.mappings, cg.project_dir);
+
+        if let Some(major) = self.nodejs {
+            writeln!(out, "  nodejs = nodejs_{major};\n")?;
+        }
+
+        let expr = format!(
+            r#"(import({}){{}}).fetchNpmDeps{{src={};hash="{FAKE_HASH}";}}"#,
+            cg.nixpkgs,
+            cg.project_src(),
+        );
         let hash = if cg.layout.has_npm_lock
-            && let Some(hash) = fod_hash(format!(
-                r#"(import({}){{}}).fetchNpmDeps{{src={};hash="{FAKE_HASH}";}}"#,
-                cg.nixpkgs, cg.src,
-            ))
-            .await
+            && let Some(hash) = cg.prefetch_hash("npmDepsHash", expr).await
         {
             hash
         } else {
@@ -34,10 +56,265 @@ impl Builder for BuildNpmPackage {
 
         writeln!(out, "  npmDepsHash = \"{hash}\";\n")?;
 
-        if !npm_has_build_script(cg.src_dir) {
+        if !npm_has_build_script(cg.project_dir) {
             writeln!(out, "  dontNpmBuild = true;\n")?;
         }
 
         Ok(out)
     }
+
+    fn extra_lambda_inputs(&self, _: &Codegen<'_>) -> Vec<String> {
+        self.nodejs
+            .map(|major| format!("nodejs_{major}"))
+            .into_iter()
+            .collect()
+    }
+
+    fn main_programs(&self, cg: &Codegen<'_>) -> Vec<String> {
+        npm_bins(cg.project_dir)
+    }
+}
+
+// stdenv.mkDerivation with the dependency fetcher and hooks of a package manager other than npm
+#[derive(Clone, Copy, Display)]
+#[display("stdenv.mkDerivation + {manager}")]
+pub struct MkNodeDerivation {
+    manager: PackageManager,
+    // major version of nodejs_XX used instead of the default nodejs
+    nodejs: Option<u32>,
+}
+
+impl MkNodeDerivation {
+    pub fn new(manager: PackageManager) -> Self {
+        Self {
+            manager,
+            nodejs: None,
+        }
+    }
+
+    // switches to a versioned nodejs if engines.node isn't satisfied by the default one
+    pub async fn detect_nodejs(self, src_dir: &Path, nixpkgs: &str) -> Self {
+        Self {
+            nodejs: npm_nodejs_major(src_dir, nixpkgs).await,
+            ..self
+        }
+    }
+
+    fn nodejs(&self) -> String {
+        match self.nodejs {
+            Some(major) => format!("nodejs_{major}"),
+            None => "nodejs".into(),
+        }
+    }
+}
+
+impl Builder for MkNodeDerivation {
+    fn function(&self) -> Cow<'static, str> {
+        "stdenv.mkDerivation".into()
+    }
+
+    fn explicit_strict_deps(&self) -> bool {
+        true
+    }
+
+    async fn after_src(&self, cg: &mut Codegen<'_>) -> Result<String> {
+        let mut out = String::new();
+        load_npm_dependencies(&mut cg.inputs, cg.mappings, cg.project_dir);
+        let has_build_script = npm_has_build_script(cg.project_dir);
+
+        let src = cg.project_src();
+        let nixpkgs = cg.nixpkgs;
+        let (attr, expr) = match self.manager {
+            PackageManager::Bun => (
+                "node_modules",
+                format!(
+                    r#"let pkgs=import({nixpkgs}){{}};in pkgs.stdenvNoCC.mkDerivation{{name="node_modules";src={src};nativeBuildInputs=[pkgs.bun pkgs.writableTmpDirAsHomeHook];dontConfigure=true;buildPhase="bun install --frozen-lockfile --ignore-scripts --no-progress";installPhase="cp -r node_modules $out";dontFixup=true;outputHash="{FAKE_HASH}";outputHashMode="recursive";}}"#,
+                ),
+            ),
+            PackageManager::Pnpm => (
+                "pnpmDeps",
+                format!(
+                    r#"(import({nixpkgs}){{}}).pnpm.fetchDeps{{pname={:?};version={:?};src={src};fetcherVersion=2;hash="{FAKE_HASH}";}}"#,
+                    cg.pname, cg.version,
+                ),
+            ),
+            PackageManager::Yarn => (
+                "yarnOfflineCache",
+                format!(
+                    r#"(import({nixpkgs}){{}}).fetchYarnDeps{{yarnLock={src}/yarn.lock;hash="{FAKE_HASH}";}}"#,
+                ),
+            ),
+            PackageManager::YarnBerry => (
+                "offlineCache",
+                format!(
+                    r#"(import({nixpkgs}){{}}).yarn-berry.fetchYarnBerryDeps{{src={src};hash="{FAKE_HASH}";}}"#,
+                ),
+            ),
+        };
+        let hash = cg
+            .prefetch_hash(attr, expr)
+            .await
+            .unwrap_or_else(|| FAKE_HASH.into());
+
+        let source_root = if cg.root.is_some() { " sourceRoot" } else { "" };
+        write!(out, "  ")?;
+        match self.manager {
+            PackageManager::Bun => {
+                let inputs = &mut cg.inputs;
+                input_macros!(inputs, "bun.lock");
+                native_build!("bun", self.nodejs(), "npmHooks.npmInstallHook");
+                writedoc! {
+                    out,
+                    r#"
+                        node_modules = stdenvNoCC.mkDerivation {{
+                            pname = "${{finalAttrs.pname}}-node_modules";
+                            inherit (finalAttrs) version src{source_root};
+
+                            nativeBuildInputs = [
+                              bun
+                              writableTmpDirAsHomeHook
+                            ];
+
+                            dontConfigure = true;
+
+                            buildPhase = ''
+                              runHook preBuild
+                              bun install --frozen-lockfile --ignore-scripts --no-progress
+                              runHook postBuild
+                            '';
+
+                            installPhase = ''
+                              runHook preInstall
+                              cp -r node_modules $out
+                              runHook postInstall
+                            '';
+
+                            dontFixup = true;
+
+                            outputHash = "{hash}";
+                            outputHashMode = "recursive";
+                          }};
+
+                    "#,
+                }?;
+            }
+            PackageManager::Pnpm => {
+                let inputs = &mut cg.inputs;
+                input_macros!(inputs, "pnpm-lock.yaml");
+                native_build!(self.nodejs(), "npmHooks.npmInstallHook", "pnpm.configHook");
+                writedoc! {
+                    out,
+                    r#"
+                        pnpmDeps = pnpm.fetchDeps {{
+                            inherit (finalAttrs) pname version src{source_root};
+                            fetcherVersion = 2;
+                            hash = "{hash}";
+                          }};
+
+                    "#,
+                }?;
+            }
+            PackageManager::Yarn => {
+                let inputs = &mut cg.inputs;
+                input_macros!(inputs, "yarn.lock");
+                native_build!(self.nodejs(), "yarnConfigHook", "yarnInstallHook");
+                if has_build_script {
+                    native_build!("yarnBuildHook");
+                }
+                writedoc! {
+                    out,
+                    r#"
+                        yarnOfflineCache = fetchYarnDeps {{
+                            yarnLock = finalAttrs.src + "/{root}yarn.lock";
+                            hash = "{hash}";
+                          }};
+
+                    "#,
+                    root = cg.root.map_or_else(String::new, |root| format!("{root}/")),
+                }?;
+            }
+            PackageManager::YarnBerry => {
+                let inputs = &mut cg.inputs;
+                input_macros!(inputs, "yarn.lock");
+                native_build!(
+                    self.nodejs(),
+                    "npmHooks.npmInstallHook",
+                    "yarn-berry.yarnBerryConfigHook"
+                );
+                writedoc! {
+                    out,
+                    r#"
+                        offlineCache = yarn-berry.fetchYarnBerryDeps {{
+                            inherit (finalAttrs) src{source_root};
+                            hash = "{hash}";
+                          }};
+
+                    "#,
+                }?;
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn extra_lambda_inputs(&self, _: &Codegen<'_>) -> Vec<String> {
+        match self.manager {
+            PackageManager::Bun => vec!["stdenvNoCC".into(), "writableTmpDirAsHomeHook".into()],
+            PackageManager::Yarn => vec!["fetchYarnDeps".into()],
+            PackageManager::Pnpm | PackageManager::YarnBerry => Vec::new(),
+        }
+    }
+
+    fn after_inputs(&self, cg: &mut Codegen<'_>) -> Result<String> {
+        let mut out = String::new();
+        if self.manager == PackageManager::Bun {
+            write!(out, "  ")?;
+            writedoc! {
+                out,
+                r#"
+                    configurePhase = ''
+                        runHook preConfigure
+                        cp -r ${{finalAttrs.node_modules}} node_modules
+                        chmod -R u+w node_modules
+                        runHook postConfigure
+                      '';
+
+                "#,
+            }?;
+        }
+
+        // yarn v1 has hooks to build and install, npmInstallHook is used for the others
+        if self.manager == PackageManager::Yarn {
+            return Ok(out);
+        }
+
+        if npm_has_build_script(cg.project_dir) {
+            write!(out, "  ")?;
+            writedoc! {
+                out,
+                r#"
+                    buildPhase = ''
+                        runHook preBuild
+                        {} build
+                        runHook postBuild
+                      '';
+
+                "#,
+                self.manager.run(),
+            }?;
+        }
+
+        // the dependencies are not managed by npm
+        writeln!(out, "  dontNpmPrune = true;\n")?;
+
+        Ok(out)
+    }
+
+    fn main_programs(&self, cg: &Codegen<'_>) -> Vec<String> {
+        npm_bins(cg.project_dir)
+    }
+
+    fn explicit_platforms(&self) -> bool {
+        true
+    }
 }
@@ -1,12 +1,21 @@
-use std::fmt::{self, Display, Formatter, Write as _};
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter, Write as _},
+    mem,
+};
 
 use anyhow::Result;
-use heck::{AsSnakeCase, ToKebabCase};
+use heck::AsSnakeCase;
 
 use crate::{
     cli::CargoVendor,
     codegen::{Builder, Codegen},
-    lang::python::{Pyproject, parse_requirements_txt},
+    inputs::Comments,
+    lang::{
+        pypi::PypiIndex,
+        python::{Pyproject, parse_requirements_txt},
+    },
+    macros::input_macros,
 };
 
 #[derive(Clone, Copy)]
@@ -22,11 +31,11 @@ impl BuildPythonPackage {
 }
 
 impl Builder for BuildPythonPackage {
-    fn function(&self) -> &'static str {
+    fn function(&self) -> Cow<'static, str> {
         if self.application {
-            "python3Packages.buildPythonApplication"
+            "python3Packages.buildPythonApplication".into()
         } else {
-            "buildPythonPackage"
+            "buildPythonPackage".into()
         }
     }
 
@@ -34,6 +43,49 @@ impl Builder for BuildPythonPackage {
         Ok("  pyproject = true;\n".into())
     }
 
+    // dependencies are loaded before the inputs are validated
+    async fn after_src(&self, cg: &mut Codegen<'_>) -> Result<String> {
+        let mut pyproject = Pyproject::from_path(cg.project_dir.join("pyproject.toml"));
+
+        if cg.project_dir.join("poetry.lock").is_file() {
+            let inputs = &mut cg.inputs;
+            input_macros!(inputs, "poetry.lock");
+            native_build!(if self.application {
+                "python3Packages.poetry-core"
+            } else {
+                "poetry-core"
+            });
+        }
+
+        pyproject.load_license(&mut cg.licenses);
+        pyproject.load_build_dependencies(&mut cg.inputs, cg.mappings, self.application);
+        pyproject.load_native_dependencies(cg.project_dir, &mut cg.inputs, self.application);
+
+        if let Some(deps) = pyproject.get_dependencies(cg.project_dir) {
+            cg.python_deps = deps;
+        }
+
+        if cg.python_deps.always.is_empty()
+            && cg.python_deps.optional.is_empty()
+            && let Some(mut deps) = parse_requirements_txt(cg.project_dir)
+        {
+            deps.groups = mem::take(&mut cg.python_deps.groups);
+            cg.python_deps = deps;
+        }
+
+        cg.python_deps
+            .always
+            .extend(cg.extra_python_deps.iter().cloned());
+        cg.python_deps
+            .load_check_dependencies(cg.project_dir, &pyproject);
+
+        if let Some(index) = PypiIndex::load(cg.nixpkgs).await {
+            index.resolve(&mut cg.python_deps);
+        }
+
+        Ok(String::new())
+    }
+
     fn cargo_deps(&self) -> Option<CargoVendor> {
         self.rust
     }
@@ -42,12 +94,7 @@ impl Builder for BuildPythonPackage {
         if self.application {
             return Vec::new();
         }
-        cg.python_deps
-            .always
-            .iter()
-            .chain(cg.python_deps.optional.values().flatten())
-            .map(|name| name.to_kebab_case())
-            .collect()
+        cg.python_deps.names().cloned().collect()
     }
 
     fn native_build_inputs_attr(&self) -> &'static str {
@@ -56,32 +103,23 @@ impl Builder for BuildPythonPackage {
 
     fn after_inputs(&self, cg: &mut Codegen<'_>) -> Result<String> {
         let mut out = String::new();
-        let mut pyproject = Pyproject::from_path(cg.src_dir.join("pyproject.toml"));
-        let import = pyproject.get_name();
-
-        if cg.src_dir.join("poetry.lock").is_file() {
-            cg.inputs.native_build_inputs.always.insert(
-                if self.application {
-                    "python3Packages.poetry-core"
-                } else {
-                    "poetry-core"
+        let import = Pyproject::from_path(cg.project_dir.join("pyproject.toml")).get_name();
+        let comments = Comments {
+            reasons: None,
+            missing: &cg.missing_inputs,
+        };
+
+        for (attr, names) in [
+            ("pythonRelaxDeps", &cg.python_deps.relax),
+            ("pythonRemoveDeps", &cg.python_deps.remove),
+        ] {
+            if !names.is_empty() {
+                writeln!(out, "  {attr} = [")?;
+                for name in names {
+                    writeln!(out, "    {name:?}")?;
                 }
-                .into(),
-            );
-        }
-
-        pyproject.load_license(&mut cg.licenses);
-        pyproject.load_build_dependencies(&mut cg.inputs, self.application);
-
-        if let Some(deps) = pyproject.get_dependencies() {
-            cg.python_deps = deps;
-        }
-
-        if cg.python_deps.always.is_empty()
-            && cg.python_deps.optional.is_empty()
-            && let Some(deps) = parse_requirements_txt(cg.src_dir)
-        {
-            cg.python_deps = deps;
+                writeln!(out, "  ];\n")?;
+            }
         }
 
         if !cg.python_deps.always.is_empty() {
@@ -92,7 +130,9 @@ impl Builder for BuildPythonPackage {
             writeln!(out, "[")?;
 
             for name in &cg.python_deps.always {
-                writeln!(out, "    {name}")?;
+                write!(out, "    {name}")?;
+                comments.write(&mut out, &format!("python3Packages.{name}"))?;
+                writeln!(out)?;
             }
             writeln!(out, "  ];\n")?;
         }
@@ -110,14 +150,18 @@ impl Builder for BuildPythonPackage {
             }
             writeln!(out, "{{\n    {extra} = [",)?;
             for name in deps {
-                writeln!(out, "      {name}")?;
+                write!(out, "      {name}")?;
+                comments.write(&mut out, &format!("python3Packages.{name}"))?;
+                writeln!(out)?;
             }
             writeln!(out, "    ];")?;
 
             for (extra, deps) in optional {
                 writeln!(out, "    {extra} = [")?;
                 for name in deps {
-                    writeln!(out, "      {name}")?;
+                    write!(out, "      {name}")?;
+                    comments.write(&mut out, &format!("python3Packages.{name}"))?;
+                    writeln!(out)?;
                 }
                 writeln!(out, "    ];")?;
             }
@@ -125,6 +169,20 @@ impl Builder for BuildPythonPackage {
             writeln!(out, "  }};\n")?;
         }
 
+        if !cg.python_deps.check.is_empty() {
+            write!(out, "  nativeCheckInputs = ")?;
+            if self.application {
+                write!(out, "with python3Packages; ")?;
+            }
+            writeln!(out, "[")?;
+            for name in &cg.python_deps.check {
+                write!(out, "    {name}")?;
+                comments.write(&mut out, &format!("python3Packages.{name}"))?;
+                writeln!(out)?;
+            }
+            writeln!(out, "  ];\n")?;
+        }
+
         writeln!(
             out,
             "  pythonImportsCheck = [\n    \"{}\"\n  ];\n",
@@ -137,9 +195,21 @@ impl Builder for BuildPythonPackage {
         self.application
     }
 
+    fn main_programs(&self, cg: &Codegen<'_>) -> Vec<String> {
+        Pyproject::from_path(cg.project_dir.join("pyproject.toml")).get_scripts()
+    }
+
     fn allow_by_name(&self) -> bool {
         self.application
     }
+
+    fn call_package(&self) -> &'static str {
+        if self.application {
+            "callPackage"
+        } else {
+            "python3Packages.callPackage"
+        }
+    }
 }
 
 impl Display for BuildPythonPackage {
@@ -1,11 +1,17 @@
-use std::fmt::{self, Display, Formatter, Write as _};
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter, Write},
+};
 
 use anyhow::Result;
 
 use crate::{
     cli::CargoVendor,
     codegen::{Builder, CargoDeps, Codegen, prepare_cargo_deps},
-    lang::rust::write_cargo_lock,
+    frontend::Frontend,
+    lang::rust::{
+        CargoFeatures, CargoMember, cargo_bin_members, cargo_bins, cargo_features, write_cargo_lock,
+    },
 };
 
 #[derive(Clone, Copy)]
@@ -20,13 +26,42 @@ impl BuildRustPackage {
 }
 
 impl Builder for BuildRustPackage {
-    fn function(&self) -> &'static str {
-        "rustPlatform.buildRustPackage"
+    fn function(&self) -> Cow<'static, str> {
+        "rustPlatform.buildRustPackage".into()
     }
 
     async fn after_src(&self, cg: &mut Codegen<'_>) -> Result<String> {
+        // the selection determines which dependencies are loaded
+        let members = cargo_bin_members(cg.project_dir);
+        if cg.cargo_members.is_none() {
+            cg.cargo_members = Some(if members.len() > 1 {
+                cg.frontend
+                    .cargo_members(members.iter().map(|member| member.name.clone()).collect())?
+            } else {
+                Vec::new()
+            });
+        }
+        let selected = cg.cargo_members.clone().unwrap_or_default();
+
+        let features = cargo_features(cg.project_dir, &selected);
+        let has_default = features.iter().any(|feature| feature == "default");
+        if cg.cargo_features.is_none() {
+            cg.cargo_features = Some(if features.iter().any(|feature| feature != "default") {
+                let mut features = cg.frontend.cargo_features(features)?;
+                // the answer is exactly the enabled features
+                let no_default_features = !features.iter().any(|feature| feature == "default");
+                features.retain(|feature| feature != "default");
+                CargoFeatures {
+                    features,
+                    no_default_features,
+                }
+            } else {
+                CargoFeatures::default()
+            });
+        }
+
         let mut out = String::new();
-        match prepare_cargo_deps(cg, self.deps).await? {
+        match prepare_cargo_deps(cg, self.deps, "cargoHash").await? {
             CargoDeps::Hash(hash) => {
                 writeln!(out, "  cargoHash = \"{hash}\";\n")?;
             }
@@ -38,8 +73,64 @@ impl Builder for BuildRustPackage {
                 write_cargo_lock(&mut out, has_cargo_lock, *resolve).await?;
             }
         }
+
+        write_members(&mut out, &members, &selected)?;
+
+        if let Some(CargoFeatures {
+            features,
+            no_default_features,
+        }) = &cg.cargo_features
+        {
+            if !features.is_empty() {
+                writeln!(out, "  buildFeatures = [")?;
+                for feature in features {
+                    writeln!(out, "    {feature:?}")?;
+                }
+                writeln!(out, "  ];\n")?;
+            }
+            if *no_default_features && has_default {
+                writeln!(out, "  buildNoDefaultFeatures = true;\n")?;
+            }
+        }
+
         Ok(out)
     }
+
+    fn main_programs(&self, cg: &Codegen<'_>) -> Vec<String> {
+        cargo_bins(
+            cg.project_dir,
+            cg.cargo_members.as_deref().unwrap_or_default(),
+        )
+    }
+}
+
+fn write_members(
+    out: &mut impl Write,
+    members: &[CargoMember],
+    selected: &[String],
+) -> fmt::Result {
+    if let [name] = selected
+        && let Some(member) = members.iter().find(|member| member.name == *name)
+        && !member.path.as_os_str().is_empty()
+    {
+        // a single member in its own directory can be built from there
+        writeln!(
+            out,
+            "  buildAndTestSubdir = {:?};\n",
+            member.path.display().to_string(),
+        )
+    } else if !selected.is_empty() {
+        writeln!(out, "  cargoBuildFlags = [")?;
+        for name in selected {
+            writeln!(out, "    \"-p\"\n    {name:?}")?;
+        }
+        writeln!(
+            out,
+            "  ];\n\n  cargoTestFlags = finalAttrs.cargoBuildFlags;\n"
+        )
+    } else {
+        Ok(())
+    }
 }
 
 impl Display for BuildRustPackage {
@@ -54,3 +145,61 @@ impl Display for BuildRustPackage {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::write_members;
+    use crate::lang::rust::CargoMember;
+
+    fn members() -> Vec<CargoMember> {
+        [("root", ""), ("foo", "crates/foo"), ("bar", "crates/bar")]
+            .into_iter()
+            .map(|(name, path)| CargoMember {
+                name: name.into(),
+                path: path.into(),
+            })
+            .collect()
+    }
+
+    fn written(selected: &[&str]) -> String {
+        let selected: Vec<_> = selected.iter().map(|name| (*name).into()).collect();
+        let mut out = String::new();
+        write_members(&mut out, &members(), &selected).unwrap();
+        out
+    }
+
+    #[test]
+    fn members_flags() {
+        assert_eq!(written(&[]), "");
+        assert_eq!(
+            written(&["foo"]),
+            "  buildAndTestSubdir = \"crates/foo\";\n\n",
+        );
+        assert_eq!(
+            written(&["root"]),
+            concat!(
+                "  cargoBuildFlags = [\n",
+                "    \"-p\"\n",
+                "    \"root\"\n",
+                "  ];\n",
+                "\n",
+                "  cargoTestFlags = finalAttrs.cargoBuildFlags;\n",
+                "\n",
+            ),
+        );
+        assert_eq!(
+            written(&["foo", "bar"]),
+            concat!(
+                "  cargoBuildFlags = [\n",
+                "    \"-p\"\n",
+                "    \"foo\"\n",
+                "    \"-p\"\n",
+                "    \"bar\"\n",
+                "  ];\n",
+                "\n",
+                "  cargoTestFlags = finalAttrs.cargoBuildFlags;\n",
+                "\n",
+            ),
+        );
+    }
+}
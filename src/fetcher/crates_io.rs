@@ -34,12 +34,21 @@ struct CrateVersion {
     yanked: bool,
 }
 
+// crates.io requires a user agent
+pub fn client() -> Result<Client> {
+    Client::builder()
+        .user_agent("https://github.com/nix-community/nix-init")
+        .build()
+        .map_err(Into::into)
+}
+
 impl Fetcher for FetchCrate {
-    async fn create_client(&self, _: AccessTokens) -> Result<Client> {
-        Client::builder()
-            .user_agent("https://github.com/nix-community/nix-init")
-            .build()
-            .map_err(Into::into)
+    fn host(&self) -> &str {
+        "crates.io"
+    }
+
+    async fn create_client(&self, _: &mut AccessTokens) -> Result<Client> {
+        client()
     }
 
     async fn get_package_info(&self, cl: &Client) -> PackageInfo {
@@ -58,7 +58,11 @@ struct Committer {
 }
 
 impl Fetcher for FetchFromGitHub {
-    async fn create_client(&self, mut tokens: AccessTokens) -> Result<Client> {
+    fn host(&self) -> &str {
+        &self.github_base
+    }
+
+    async fn create_client(&self, tokens: &mut AccessTokens) -> Result<Client> {
         let mut headers = HeaderMap::new();
         tokens.insert_header(&mut headers, &self.github_base).await;
         Client::builder()
@@ -47,7 +47,11 @@ struct Commit {
 }
 
 impl Fetcher for FetchFromGitLab {
-    async fn create_client(&self, mut tokens: AccessTokens) -> Result<Client> {
+    fn host(&self) -> &str {
+        &self.domain
+    }
+
+    async fn create_client(&self, tokens: &mut AccessTokens) -> Result<Client> {
         let mut headers = HeaderMap::new();
         tokens.insert_header(&mut headers, &self.domain).await;
         Client::builder()
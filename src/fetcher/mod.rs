@@ -25,7 +25,9 @@ use crate::{
 
 #[enum_dispatch]
 pub trait Fetcher {
-    async fn create_client(&self, tokens: AccessTokens) -> Result<Client>;
+    fn host(&self) -> &str;
+
+    async fn create_client(&self, tokens: &mut AccessTokens) -> Result<Client>;
 
     async fn get_package_info(&self, cl: &Client) -> PackageInfo;
 
@@ -69,6 +71,13 @@ pub struct Revisions {
     pub versions: FxHashMap<String, Version>,
 }
 
+// clients are shared between packages from the same host
+// so access tokens only have to be resolved once
+pub struct Clients {
+    clients: FxHashMap<String, Client>,
+    tokens: AccessTokens,
+}
+
 pub struct PackageInfo {
     pub pname: String,
     pub description: String,
@@ -80,6 +89,36 @@ pub struct PackageInfo {
     pub revisions: Revisions,
 }
 
+impl Clients {
+    pub fn new(tokens: AccessTokens) -> Self {
+        Self {
+            clients: FxHashMap::default(),
+            tokens,
+        }
+    }
+
+    pub async fn get(&mut self, fetcher: &FetcherDispatch) -> Result<Client> {
+        if let Some(cl) = self.clients.get(fetcher.host()) {
+            return Ok(cl.clone());
+        }
+
+        let cl = fetcher.create_client(&mut self.tokens).await?;
+        self.clients.insert(fetcher.host().into(), cl.clone());
+        Ok(cl)
+    }
+
+    // the same client as fetchCrate, used to download crates
+    pub fn crates_io(&mut self) -> Result<Client> {
+        if let Some(cl) = self.clients.get("crates.io") {
+            return Ok(cl.clone());
+        }
+
+        let cl = crates_io::client()?;
+        self.clients.insert("crates.io".into(), cl.clone());
+        Ok(cl)
+    }
+}
+
 pub async fn json<T: for<'a> Deserialize<'a>>(cl: &Client, url: impl IntoUrl) -> Option<T> {
     cl.get(url)
         .send()
@@ -46,7 +46,11 @@ struct Release {
 }
 
 impl Fetcher for FetchPypi {
-    async fn create_client(&self, _: AccessTokens) -> Result<Client> {
+    fn host(&self) -> &str {
+        "pypi.org"
+    }
+
+    async fn create_client(&self, _: &mut AccessTokens) -> Result<Client> {
         Ok(Client::new())
     }
 
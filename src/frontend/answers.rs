@@ -0,0 +1,240 @@
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::{
+    codegen::{Builder, BuilderCandidate},
+    fetcher::{Revisions, Version},
+    frontend::{Frontend, readline::Readline},
+};
+
+pub struct Answers {
+    answers: Box<AnswersFile>,
+    // prompt for missing answers instead of failing
+    fallback: Option<Readline>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct AnswersFile {
+    url: Option<String>,
+    rev: Option<String>,
+    fetch_submodules: Option<bool>,
+    version: Option<String>,
+    pname: Option<String>,
+    builder: Option<String>,
+    cargo_members: Option<Vec<String>>,
+    cargo_features: Option<Vec<String>>,
+    main_program: Option<String>,
+    output: Option<PathBuf>,
+    overwrite: Option<bool>,
+}
+
+impl Answers {
+    pub fn new(path: &Path, headless: bool) -> Result<Self> {
+        let content = read_to_string(path).context("failed to read answers file")?;
+        let answers = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&content).context("failed to parse answers file")?
+        } else {
+            toml::from_str(&content).context("failed to parse answers file")?
+        };
+
+        Ok(Self {
+            answers,
+            fallback: if headless {
+                None
+            } else {
+                Some(Readline::new()?)
+            },
+        })
+    }
+
+    fn fallback(&mut self, name: &str) -> Result<&mut Readline> {
+        match &mut self.fallback {
+            Some(readline) => Ok(readline),
+            None => bail!("{name} is not specified in the answers file"),
+        }
+    }
+}
+
+impl Frontend for Answers {
+    fn url(&mut self) -> Result<String> {
+        match self.answers.url.take() {
+            Some(url) => Ok(url),
+            None => self.fallback("url")?.url(),
+        }
+    }
+
+    fn rev(&mut self, revs: Option<Revisions>) -> Result<(String, Option<Version>)> {
+        match self.answers.rev.take() {
+            Some(rev) => {
+                let version = revs.and_then(|mut revs| revs.versions.remove(&rev));
+                Ok((rev, version))
+            }
+            None => self.fallback("rev")?.rev(revs),
+        }
+    }
+
+    fn fetch_submodules(&mut self) -> Result<bool> {
+        match self.answers.fetch_submodules {
+            Some(submodules) => Ok(submodules),
+            None => self.fallback("fetch-submodules")?.fetch_submodules(),
+        }
+    }
+
+    fn version(&mut self, version: &str) -> Result<String> {
+        match self.answers.version.take() {
+            Some(version) => Ok(version),
+            None => self.fallback("version")?.version(version),
+        }
+    }
+
+    fn pname(&mut self, pname: Option<String>) -> Result<String> {
+        match self.answers.pname.take() {
+            Some(pname) => Ok(pname),
+            None => self.fallback("pname")?.pname(pname),
+        }
+    }
+
+    fn builder(&mut self, builders: Vec<BuilderCandidate>) -> Result<BuilderCandidate> {
+        let Some(answer) = &self.answers.builder else {
+            return self.fallback("builder")?.builder(builders);
+        };
+
+        match find_builder(&builders, answer) {
+            Some(builder) => Ok(builder),
+            None => {
+                let options = builders.iter().map(ToString::to_string);
+                bail!(
+                    "builder {answer:?} from the answers file is not available, options are: {}",
+                    options.collect::<Vec<_>>().join(", "),
+                );
+            }
+        }
+    }
+
+    fn cargo_members(&mut self, members: Vec<String>) -> Result<Vec<String>> {
+        match &self.answers.cargo_members {
+            Some(answer) => Ok(answer.clone()),
+            None => self.fallback("cargo-members")?.cargo_members(members),
+        }
+    }
+
+    fn cargo_features(&mut self, features: Vec<String>) -> Result<Vec<String>> {
+        match &self.answers.cargo_features {
+            Some(answer) => Ok(answer.clone()),
+            None => self.fallback("cargo-features")?.cargo_features(features),
+        }
+    }
+
+    fn main_program(&mut self, programs: Vec<String>) -> Result<String> {
+        match &self.answers.main_program {
+            Some(program) => Ok(program.clone()),
+            None => self.fallback("main-program")?.main_program(programs),
+        }
+    }
+
+    fn output(&mut self, pname: &str, builder: &impl Builder) -> Result<PathBuf> {
+        match self.answers.output.take() {
+            Some(output) => Ok(output),
+            None => self.fallback("output")?.output(pname, builder),
+        }
+    }
+
+    fn overwrite(&mut self, path: &Path) -> Result<bool> {
+        match self.answers.overwrite {
+            Some(overwrite) => Ok(overwrite),
+            None => self.fallback("overwrite")?.overwrite(path),
+        }
+    }
+}
+
+// matches the exact option (e.g. `buildRustPackage - cargoLock` or `buildGoModule (cli)`),
+// or the first option using the function (e.g. `buildRustPackage`)
+fn find_builder(builders: &[BuilderCandidate], answer: &str) -> Option<BuilderCandidate> {
+    builders
+        .iter()
+        .find(|candidate| candidate.to_string() == answer)
+        .or_else(|| {
+            builders.iter().find(|candidate| {
+                let function = candidate.builder.function();
+                function == answer || function.rsplit_once('.').is_some_and(|(_, f)| f == answer)
+            })
+        })
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnswersFile, find_builder};
+    use crate::{
+        cli::CargoVendor,
+        codegen::{
+            BuilderCandidate, BuilderDispatch, drv::MkDerivation, go::BuildGoModule,
+            rust::BuildRustPackage,
+        },
+    };
+
+    #[test]
+    fn parse() {
+        let answers: AnswersFile = toml::from_str(
+            r#"
+                rev = "v1.0.0"
+                fetch-submodules = false
+                builder = "buildRustPackage - cargoLock"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            answers,
+            AnswersFile {
+                rev: Some("v1.0.0".into()),
+                fetch_submodules: Some(false),
+                builder: Some("buildRustPackage - cargoLock".into()),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let builders: Vec<BuilderDispatch> = vec![
+            BuildRustPackage::new(CargoVendor::FetchCargoVendor).into(),
+            MkDerivation::new(Some(CargoVendor::FetchCargoVendor)).into(),
+            BuildRustPackage::new(CargoVendor::ImportCargoLock).into(),
+            MkDerivation::new(None).into(),
+        ];
+        let builders: Vec<_> = [(BuildGoModule::default().into(), Some("cli".into()))]
+            .into_iter()
+            .chain(builders.into_iter().map(|builder| (builder, None)))
+            .map(|(builder, root)| BuilderCandidate { builder, root })
+            .collect();
+
+        let find = |answer| find_builder(&builders, answer).map(|builder| builder.to_string());
+        assert_eq!(
+            find("buildRustPackage - cargoLock").as_deref(),
+            Some("buildRustPackage - cargoLock"),
+        );
+        assert_eq!(
+            find("buildRustPackage").as_deref(),
+            Some("buildRustPackage - cargoHash"),
+        );
+        assert_eq!(
+            find("stdenv.mkDerivation").as_deref(),
+            Some("stdenv.mkDerivation"),
+        );
+        assert_eq!(
+            find("mkDerivation").as_deref(),
+            Some("stdenv.mkDerivation + fetchCargoVendor"),
+        );
+        assert_eq!(
+            find("buildGoModule (cli)").as_deref(),
+            Some("buildGoModule (cli)"),
+        );
+        assert_eq!(find("buildPythonPackage"), None);
+    }
+}
@@ -4,13 +4,17 @@ use anyhow::{Result, bail};
 use tracing::error;
 
 use crate::{
-    codegen::{Builder, BuilderDispatch},
+    codegen::{Builder, BuilderCandidate},
     fetcher::{Revisions, Version},
     frontend::Frontend,
     utils::by_name_path,
 };
 
-pub struct Headless;
+pub struct Headless {
+    // output to a directory named after the package instead of the current directory,
+    // so packages from the same batch don't overwrite each other
+    pub pname_dir: bool,
+}
 
 impl Frontend for Headless {
     fn url(&mut self) -> Result<String> {
@@ -37,13 +41,29 @@ impl Frontend for Headless {
         Ok(pname.unwrap_or_default())
     }
 
-    fn builder(&mut self, builders: Vec<BuilderDispatch>) -> Result<BuilderDispatch> {
-        Ok(builders[0])
+    fn builder(&mut self, mut builders: Vec<BuilderCandidate>) -> Result<BuilderCandidate> {
+        Ok(builders.swap_remove(0))
+    }
+
+    fn cargo_members(&mut self, _: Vec<String>) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn cargo_features(&mut self, features: Vec<String>) -> Result<Vec<String>> {
+        Ok(features
+            .into_iter()
+            .filter(|feature| feature == "default")
+            .collect())
+    }
+
+    fn main_program(&mut self, mut programs: Vec<String>) -> Result<String> {
+        Ok(programs.swap_remove(0))
     }
 
     fn output(&mut self, pname: &str, builder: &impl Builder) -> Result<PathBuf> {
         Ok(match by_name_path(pname, builder) {
             Some(path) => path.into(),
+            None if self.pname_dir => PathBuf::from(format!("{pname}/")),
             None => PathBuf::from("."),
         })
     }
@@ -1,3 +1,4 @@
+mod answers;
 mod headless;
 mod readline;
 
@@ -7,9 +8,9 @@ use anyhow::Result;
 use enum_dispatch::enum_dispatch;
 
 use crate::{
-    codegen::{Builder, BuilderDispatch},
+    codegen::{Builder, BuilderCandidate},
     fetcher::{Revisions, Version},
-    frontend::{headless::Headless, readline::Readline},
+    frontend::{answers::Answers, headless::Headless, readline::Readline},
 };
 
 #[enum_dispatch]
@@ -24,7 +25,15 @@ pub trait Frontend {
 
     fn pname(&mut self, pname: Option<String>) -> Result<String>;
 
-    fn builder(&mut self, builders: Vec<BuilderDispatch>) -> Result<BuilderDispatch>;
+    fn builder(&mut self, builders: Vec<BuilderCandidate>) -> Result<BuilderCandidate>;
+
+    // returns an empty list to build the default members
+    fn cargo_members(&mut self, members: Vec<String>) -> Result<Vec<String>>;
+
+    // returns exactly the features to enable, including "default" to keep the default ones
+    fn cargo_features(&mut self, features: Vec<String>) -> Result<Vec<String>>;
+
+    fn main_program(&mut self, programs: Vec<String>) -> Result<String>;
 
     fn output(&mut self, pname: &str, builder: &impl Builder) -> Result<PathBuf>;
 
@@ -33,6 +42,7 @@ pub trait Frontend {
 
 #[enum_dispatch(Frontend)]
 pub enum FrontendDispatch {
+    Answers(Answers),
     Headless(Headless),
     Readline(Readline),
 }
@@ -47,7 +57,15 @@ impl FrontendDispatch {
 }
 
 pub fn headless() -> FrontendDispatch {
-    Headless.into()
+    Headless { pname_dir: false }.into()
+}
+
+pub fn headless_batch() -> FrontendDispatch {
+    Headless { pname_dir: true }.into()
+}
+
+pub fn answers(path: &Path, headless: bool) -> Result<FrontendDispatch> {
+    Answers::new(path, headless).map(Into::into)
 }
 
 pub fn readline() -> Result<FrontendDispatch> {
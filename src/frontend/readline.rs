@@ -15,7 +15,7 @@ use rustyline::{
 };
 
 use crate::{
-    codegen::{Builder, BuilderDispatch},
+    codegen::{Builder, BuilderCandidate},
     fetcher::{Revisions, Version},
     frontend::Frontend,
     utils::by_name_path,
@@ -31,7 +31,9 @@ enum Prompter {
     Revision(Revisions),
     NonEmpty,
     YesNo,
-    Builder(Vec<BuilderDispatch>),
+    Builder(Vec<BuilderCandidate>),
+    Choice(Vec<String>),
+    Multiple(Vec<String>),
 }
 
 impl Readline {
@@ -110,7 +112,7 @@ impl Frontend for Readline {
         })
     }
 
-    fn builder(&mut self, builders: Vec<BuilderDispatch>) -> Result<BuilderDispatch> {
+    fn builder(&mut self, builders: Vec<BuilderCandidate>) -> Result<BuilderCandidate> {
         self.editor.set_helper(Some(Prompter::Builder(builders)));
         let builder = self
             .editor
@@ -118,11 +120,50 @@ impl Frontend for Readline {
         let Some(Prompter::Builder(builders)) = self.editor.helper_mut() else {
             unreachable!();
         };
-        Ok(*builder
+        let i = builder
             .parse()
             .ok()
-            .and_then(|i: usize| builders.get(i))
-            .unwrap_or_else(|| &builders[0]))
+            .filter(|&i: &usize| i < builders.len())
+            .unwrap_or_default();
+        Ok(builders.swap_remove(i))
+    }
+
+    fn cargo_members(&mut self, members: Vec<String>) -> Result<Vec<String>> {
+        self.editor.set_helper(Some(Prompter::Multiple(members)));
+        let members = self.editor.readline(&prompt(
+            "Which workspace members should be built? (space separated, empty for the default ones)",
+        ))?;
+        Ok(members.split_whitespace().map(Into::into).collect())
+    }
+
+    fn cargo_features(&mut self, features: Vec<String>) -> Result<Vec<String>> {
+        // the answer is exactly the enabled features, so default is preselected
+        let initial = if features.iter().any(|feature| feature == "default") {
+            "default "
+        } else {
+            ""
+        };
+        self.editor.set_helper(Some(Prompter::Multiple(features)));
+        let features = self.editor.readline_with_initial(
+            &prompt("Which features should be enabled? (space separated, only the listed ones are enabled, list default to keep the default ones)"),
+            (initial, ""),
+        )?;
+        Ok(features.split_whitespace().map(Into::into).collect())
+    }
+
+    fn main_program(&mut self, programs: Vec<String>) -> Result<String> {
+        self.editor.set_helper(Some(Prompter::Choice(programs)));
+        let program = self
+            .editor
+            .readline(&prompt("Which program is the main one?"))?;
+        let Some(Prompter::Choice(programs)) = self.editor.helper_mut() else {
+            unreachable!();
+        };
+        Ok(if program.is_empty() {
+            programs.swap_remove(0)
+        } else {
+            program
+        })
     }
 
     fn output(&mut self, pname: &str, builder: &impl Builder) -> Result<PathBuf> {
@@ -185,6 +226,31 @@ impl Completer for Prompter {
                     })
                     .collect(),
             )),
+            Prompter::Multiple(options) => {
+                // complete the word under the cursor
+                let start = line[.. pos].rfind(' ').map_or(0, |i| i + 1);
+                Ok((
+                    start,
+                    options
+                        .iter()
+                        .filter(|option| option.starts_with(&line[start .. pos]))
+                        .map(|option| Pair {
+                            display: option.clone(),
+                            replacement: option.clone(),
+                        })
+                        .collect(),
+                ))
+            }
+            Prompter::Choice(choices) => Ok((
+                0,
+                choices
+                    .iter()
+                    .map(|choice| Pair {
+                        display: choice.clone(),
+                        replacement: choice.clone(),
+                    })
+                    .collect(),
+            )),
         }
     }
 }
@@ -247,6 +313,19 @@ impl Hinter for Prompter {
             } else {
                 "  press <tab> to see options".yellow().italic().to_string()
             })),
+
+            Prompter::Multiple(_) => line
+                .is_empty()
+                .then(|| SimpleHint("  press <tab> to see options".yellow().italic().to_string())),
+
+            Prompter::Choice(choices) => line.is_empty().then(|| {
+                SimpleHint(
+                    format_args!("  ({}, press <tab> to see options)", choices[0])
+                        .blue()
+                        .italic()
+                        .to_string(),
+                )
+            }),
         }
     }
 }
@@ -294,6 +373,22 @@ impl Validator for Prompter {
                     ValidationResult::Invalid(None)
                 }
             }
+
+            Prompter::Choice(choices) => {
+                ValidationResult::Valid(ctx.input().is_empty().then(|| choices[0].clone()))
+            }
+
+            Prompter::Multiple(options) => {
+                if ctx
+                    .input()
+                    .split_whitespace()
+                    .all(|input| options.iter().any(|option| option == input))
+                {
+                    ValidationResult::Valid(None)
+                } else {
+                    ValidationResult::Invalid(Some("  unknown option".into()))
+                }
+            }
         })
     }
 }
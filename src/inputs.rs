@@ -1,19 +1,23 @@
 use std::{
     borrow::Cow,
     collections::{BTreeMap, BTreeSet},
-    fmt::Write,
+    fmt::{Display, Write},
 };
 
 use anyhow::Result;
+use itertools::Itertools;
+use serde::Serialize;
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct AllInputs {
     pub native_build_inputs: Inputs,
     pub build_inputs: Inputs,
     pub env: BTreeMap<Cow<'static, str>, (Cow<'static, str>, Vec<String>)>,
+    // the dependencies or files that caused each input or environment variable to be added
+    pub reasons: BTreeMap<String, BTreeSet<String>>,
 }
 
-#[derive(Default, Debug)]
+#[derive(Clone, Default, Debug, Serialize)]
 pub struct Inputs {
     pub always: BTreeSet<String>,
     pub darwin: BTreeSet<String>,
@@ -24,6 +28,31 @@ pub struct Inputs {
     pub x86_64_linux: BTreeSet<String>,
 }
 
+impl AllInputs {
+    pub fn add_reason(&mut self, name: &str, reason: impl Display) {
+        self.reasons
+            .entry(name.into())
+            .or_default()
+            .insert(reason.to_string());
+    }
+}
+
+impl Inputs {
+    pub fn is_empty(&self) -> bool {
+        [
+            &self.always,
+            &self.darwin,
+            &self.aarch64_darwin,
+            &self.x86_64_darwin,
+            &self.linux,
+            &self.aarch64_linux,
+            &self.x86_64_linux,
+        ]
+        .iter()
+        .all(|inputs| inputs.is_empty())
+    }
+}
+
 // "all" here refers to all systems
 // does not write out python inputs or nix-update-script
 pub fn write_all_lambda_inputs(
@@ -90,7 +119,20 @@ pub fn write_lambda_input(
     Ok(())
 }
 
-pub fn write_inputs(out: &mut impl Write, inputs: &Inputs, name: &'static str) -> Result<()> {
+// trailing comments for inputs
+pub struct Comments<'a> {
+    // written if specified
+    pub reasons: Option<&'a BTreeMap<String, BTreeSet<String>>>,
+    // inputs that don't exist in nixpkgs
+    pub missing: &'a BTreeSet<String>,
+}
+
+pub fn write_inputs(
+    out: &mut impl Write,
+    inputs: &Inputs,
+    name: &'static str,
+    comments: &Comments,
+) -> Result<()> {
     write!(out, "  {name} =")?;
 
     let mut inputs = [
@@ -119,12 +161,12 @@ pub fn write_inputs(out: &mut impl Write, inputs: &Inputs, name: &'static str) -
 
     if let Some((prefix, inputs)) = inputs.next() {
         write!(out, " {prefix}")?;
-        write_input_list(out, inputs)?;
+        write_input_list(out, inputs, comments)?;
     }
 
     for (prefix, inputs) in inputs {
         write!(out, "\n  ++ {prefix}")?;
-        write_input_list(out, inputs)?;
+        write_input_list(out, inputs, comments)?;
     }
 
     writeln!(out, ";\n")?;
@@ -132,11 +174,96 @@ pub fn write_inputs(out: &mut impl Write, inputs: &Inputs, name: &'static str) -
     Ok(())
 }
 
-fn write_input_list(out: &mut impl Write, inputs: &BTreeSet<String>) -> Result<()> {
+fn write_input_list(
+    out: &mut impl Write,
+    inputs: &BTreeSet<String>,
+    comments: &Comments,
+) -> Result<()> {
     writeln!(out, "[")?;
     for input in inputs {
-        writeln!(out, "    {input}")?;
+        write!(out, "    {input}")?;
+        comments.write(out, input)?;
+        writeln!(out)?;
     }
     write!(out, "  ]")?;
     Ok(())
 }
+
+impl Comments<'_> {
+    pub fn write(&self, out: &mut impl Write, name: &str) -> Result<()> {
+        let mut comments = Vec::new();
+        if let Some(reasons) = self.reasons.and_then(|reasons| reasons.get(name)) {
+            comments.push(reasons.iter().join(", "));
+        }
+        if self.missing.contains(name) {
+            comments.push("FIXME: nix-init did not find this in nixpkgs".into());
+        }
+
+        if !comments.is_empty() {
+            write!(out, " # {}", comments.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{AllInputs, Comments, write_inputs};
+    use crate::macros::input_macros;
+
+    #[test]
+    fn reasons() {
+        let mut inputs = AllInputs::default();
+        let inputs = &mut inputs;
+        {
+            input_macros!(inputs, "gtk4-sys");
+            native_build!("pkg-config");
+            build!("gtk4");
+        }
+        {
+            input_macros!(inputs, "openssl-sys");
+            native_build!("pkg-config");
+            build!("openssl");
+        }
+        {
+            input_macros!(inputs, "foo-sys");
+            build!("libfoo");
+        }
+
+        let missing = BTreeSet::from(["libfoo".into()]);
+        let comments = Comments {
+            reasons: Some(&inputs.reasons),
+            missing: &missing,
+        };
+
+        let mut out = String::new();
+        write_inputs(&mut out, &inputs.build_inputs, "buildInputs", &comments).unwrap();
+        write_inputs(
+            &mut out,
+            &inputs.native_build_inputs,
+            "nativeBuildInputs",
+            &comments,
+        )
+        .unwrap();
+
+        assert_eq!(
+            out,
+            [
+                "  buildInputs = [",
+                "    gtk4 # gtk4-sys",
+                "    libfoo # foo-sys; FIXME: nix-init did not find this in nixpkgs",
+                "    openssl # openssl-sys",
+                "  ];",
+                "",
+                "  nativeBuildInputs = [",
+                "    pkg-config # gtk4-sys, openssl-sys",
+                "  ];",
+                "",
+                "",
+            ]
+            .join("\n"),
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::{fs::read_to_string, path::Path};
+
+use regex::Regex;
+use tracing::warn;
+
+use crate::{
+    inputs::AllInputs,
+    lang::{
+        go::walk_go_files,
+        pkg_config::{PkgConfigIndex, library_input, pkg_config_input},
+    },
+    macros::input_macros,
+    utils::ResultExt,
+};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Cgo {
+    // whether any of the sources has `import "C"`
+    pub enabled: bool,
+    pub directives: Vec<CgoDirective>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CgoDirective {
+    pub platform: CgoPlatform,
+    pub kind: CgoDirectiveKind,
+    pub value: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CgoPlatform {
+    Always,
+    Darwin,
+    Linux,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CgoDirectiveKind {
+    // `#cgo LDFLAGS: -lfoo`
+    Library,
+    // `#cgo pkg-config: foo`
+    PkgConfig,
+}
+
+const GOOS: &[&str] = &[
+    "aix",
+    "android",
+    "darwin",
+    "dragonfly",
+    "freebsd",
+    "illumos",
+    "ios",
+    "js",
+    "linux",
+    "netbsd",
+    "openbsd",
+    "plan9",
+    "solaris",
+    "wasip1",
+    "windows",
+];
+
+// scans the go sources, including vendored modules, for cgo usage
+pub fn scan_cgo(src_dir: &Path) -> Cgo {
+    let (Some(import), Some(directive)) = (
+        Regex::new(r#"(?m)^\s*import\s+(?:\w+\s+)?"C"|^\s*"C"\s*$"#).ok_inspect(|e| warn!("{e}")),
+        Regex::new(r"(?m)^\s*(?://)?\s*#cgo\s+([^:]*?)\s*(pkg-config|LDFLAGS):(.*)$")
+            .ok_inspect(|e| warn!("{e}")),
+    ) else {
+        return Cgo::default();
+    };
+
+    let mut cgo = Cgo::default();
+    walk_go_files(src_dir, &mut |path| {
+        let Some(file_platform) = file_platform(path) else {
+            return;
+        };
+        let Ok(src) = read_to_string(path) else {
+            return;
+        };
+        if !import.is_match(&src) {
+            return;
+        }
+        cgo.enabled = true;
+
+        for caps in directive.captures_iter(&src) {
+            let Some(platform) = constraint_platform(&caps[1]) else {
+                continue;
+            };
+            let platform = match (file_platform, platform) {
+                (CgoPlatform::Always, platform) => platform,
+                (platform, CgoPlatform::Always) => platform,
+                (x, y) if x == y => x,
+                _ => continue,
+            };
+
+            let (kind, values) = if &caps[2] == "pkg-config" {
+                let values = caps[3]
+                    .split_whitespace()
+                    .filter(|module| !module.starts_with('-'))
+                    .map(Into::into)
+                    .collect();
+                (CgoDirectiveKind::PkgConfig, values)
+            } else {
+                let values = caps[3]
+                    .split_whitespace()
+                    .filter_map(|flag| flag.strip_prefix("-l"))
+                    .filter(|lib| !lib.is_empty())
+                    .map(Into::into)
+                    .collect::<Vec<_>>();
+                (CgoDirectiveKind::Library, values)
+            };
+
+            for value in values {
+                let directive = CgoDirective {
+                    platform,
+                    kind,
+                    value,
+                };
+                if !cgo.directives.contains(&directive) {
+                    cgo.directives.push(directive);
+                }
+            }
+        }
+    });
+
+    cgo
+}
+
+pub async fn load_cgo_dependencies(inputs: &mut AllInputs, nixpkgs: &str, cgo: &Cgo) {
+    let index = if cgo
+        .directives
+        .iter()
+        .any(|directive| directive.kind == CgoDirectiveKind::PkgConfig)
+    {
+        PkgConfigIndex::load(nixpkgs).await
+    } else {
+        None
+    };
+
+    for CgoDirective {
+        platform,
+        kind,
+        value,
+    } in &cgo.directives
+    {
+        let input = match kind {
+            CgoDirectiveKind::Library => {
+                let Some(input) = library_input(value) else {
+                    continue;
+                };
+                input.into()
+            }
+            CgoDirectiveKind::PkgConfig => match &index {
+                Some(index) => index.get(value),
+                None => pkg_config_input(value),
+            },
+        };
+
+        let directive = match kind {
+            CgoDirectiveKind::Library => "LDFLAGS",
+            CgoDirectiveKind::PkgConfig => "pkg-config",
+        };
+        input_macros!(inputs, format_args!("#cgo {directive}: {value}"));
+        if *kind == CgoDirectiveKind::PkgConfig {
+            native_build!("pkg-config");
+        }
+        match platform {
+            CgoPlatform::Always => build!(input),
+            CgoPlatform::Darwin => build!(input; darwin),
+            CgoPlatform::Linux => build!(input; linux),
+        }
+    }
+}
+
+// the platform a file is restricted to by its name, e.g. foo_linux.go or foo_darwin_arm64.go
+// returns None for files that are only built on other operating systems
+fn file_platform(path: &Path) -> Option<CgoPlatform> {
+    let stem = path.file_stem()?.to_str()?;
+    let mut parts = stem.rsplit('_');
+    let last = parts.next()?;
+    let goos = if GOOS.contains(&last) {
+        last
+    } else {
+        match parts.next() {
+            Some(goos) if GOOS.contains(&goos) => goos,
+            _ => return Some(CgoPlatform::Always),
+        }
+    };
+    os_platform(goos)
+}
+
+// e.g. `linux,amd64 darwin`, where spaces mean OR and commas mean AND
+fn constraint_platform(constraint: &str) -> Option<CgoPlatform> {
+    if constraint.is_empty() {
+        return Some(CgoPlatform::Always);
+    }
+
+    let mut platforms = constraint.split_whitespace().filter_map(|terms| {
+        let mut platform = CgoPlatform::Always;
+        for term in terms.split(',') {
+            if term.starts_with('!') || !GOOS.contains(&term) {
+                continue;
+            }
+            platform = match (platform, os_platform(term)?) {
+                (CgoPlatform::Always, platform) => platform,
+                (x, y) if x == y => x,
+                _ => return None,
+            };
+        }
+        Some(platform)
+    });
+
+    let first = platforms.next()?;
+    Some(if platforms.all(|platform| platform == first) {
+        first
+    } else {
+        CgoPlatform::Always
+    })
+}
+
+fn os_platform(goos: &str) -> Option<CgoPlatform> {
+    match goos {
+        "darwin" => Some(CgoPlatform::Darwin),
+        "linux" => Some(CgoPlatform::Linux),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use indoc::indoc;
+    use tempfile::tempdir;
+
+    use super::{Cgo, CgoDirective, CgoDirectiveKind, CgoPlatform, constraint_platform, scan_cgo};
+
+    #[test]
+    fn constraints() {
+        assert_eq!(constraint_platform(""), Some(CgoPlatform::Always));
+        assert_eq!(constraint_platform("linux"), Some(CgoPlatform::Linux));
+        assert_eq!(constraint_platform("linux,amd64"), Some(CgoPlatform::Linux));
+        assert_eq!(
+            constraint_platform("darwin linux"),
+            Some(CgoPlatform::Always)
+        );
+        assert_eq!(constraint_platform("!windows"), Some(CgoPlatform::Always));
+        assert_eq!(constraint_platform("windows"), None);
+        assert_eq!(
+            constraint_platform("windows darwin"),
+            Some(CgoPlatform::Darwin)
+        );
+    }
+
+    #[test]
+    fn directives() {
+        let dir = tempdir().unwrap();
+        let src = dir.path();
+        create_dir_all(src.join("internal/gui")).unwrap();
+        write(
+            src.join("internal/gui/gui.go"),
+            indoc! {r#"
+                package gui
+
+                // #cgo pkg-config: gtk4 libadwaita-1
+                // #cgo linux LDFLAGS: -lX11 -lm -L/usr/lib
+                // #cgo windows LDFLAGS: -lgdi32
+                // #include <gtk/gtk.h>
+                import "C"
+            "#},
+        )
+        .unwrap();
+        write(
+            src.join("internal/gui/notify_darwin.go"),
+            indoc! {r#"
+                package gui
+
+                /*
+                #cgo LDFLAGS: -framework Cocoa -lobjc
+                */
+                import "C"
+            "#},
+        )
+        .unwrap();
+        write(
+            src.join("internal/gui/gui_test.go"),
+            indoc! {r#"
+                package gui
+
+                // #cgo LDFLAGS: -lcheck
+                import "C"
+            "#},
+        )
+        .unwrap();
+
+        let directive = |platform, kind, value: &str| CgoDirective {
+            platform,
+            kind,
+            value: value.into(),
+        };
+        let mut cgo = scan_cgo(src);
+        cgo.directives
+            .sort_unstable_by(|x, y| x.value.cmp(&y.value));
+        assert_eq!(
+            cgo,
+            Cgo {
+                enabled: true,
+                directives: vec![
+                    directive(CgoPlatform::Linux, CgoDirectiveKind::Library, "X11"),
+                    directive(CgoPlatform::Always, CgoDirectiveKind::PkgConfig, "gtk4"),
+                    directive(
+                        CgoPlatform::Always,
+                        CgoDirectiveKind::PkgConfig,
+                        "libadwaita-1",
+                    ),
+                    directive(CgoPlatform::Linux, CgoDirectiveKind::Library, "m"),
+                    directive(CgoPlatform::Darwin, CgoDirectiveKind::Library, "objc"),
+                ],
+            },
+        );
+    }
+
+    #[test]
+    fn pure() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path().join("main.go"),
+            indoc! {r#"
+                package main
+
+                import (
+                    "fmt"
+                )
+
+                // "C" is not imported here
+                func main() {
+                    fmt.Println("C")
+                }
+            "#},
+        )
+        .unwrap();
+
+        assert_eq!(scan_cgo(dir.path()), Cgo::default());
+    }
+}
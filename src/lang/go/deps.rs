@@ -3,7 +3,7 @@ use semver::Version;
 use crate::{inputs::AllInputs, lang::go::GoPackage, macros::input_macros};
 
 pub(super) fn load_go_dependency(inputs: &mut AllInputs, pkg: GoPackage<'_>) {
-    input_macros!(inputs);
+    input_macros!(inputs, pkg.name);
 
     match pkg.name {
         // keep-sorted start group_start_regex=^\s*"
@@ -7,30 +7,151 @@ use serde::Deserialize;
 use serde_with::{OneOrMany, serde_as};
 use tracing::warn;
 
-use crate::utils::ResultExt;
+use crate::{inputs::AllInputs, lang::go::go_binaries, utils::ResultExt};
 
 #[derive(Deserialize)]
 struct GoReleaser {
     #[serde(default)]
-    builds: Vec<Build>,
+    builds: Vec<GoReleaserBuild>,
 }
 
+// https://goreleaser.com/customization/builds/go
+// mod_timestamp is not read, the nix store normalizes file timestamps anyway
 #[serde_as]
-#[derive(Deserialize)]
-struct Build {
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct GoReleaserBuild {
+    binary: Option<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    env: Vec<String>,
+    #[serde_as(as = "OneOrMany<_>")]
+    flags: Vec<String>,
     #[serde_as(as = "Option<OneOrMany<_>>")]
     ldflags: Option<Vec<String>>,
+    main: Option<String>,
+    skip: Option<serde_yaml::Value>,
+    #[serde_as(as = "OneOrMany<_>")]
+    tags: Vec<String>,
+}
+
+impl GoReleaserBuild {
+    // the directory of the main package relative to `src_dir`
+    fn main(&self) -> Option<String> {
+        normalize_main(self.main.as_deref().unwrap_or("."))
+    }
+
+    fn builds_program(&self, src_dir: &Path, program: &str) -> bool {
+        self.binary.as_deref() == Some(program)
+            || self
+                .main()
+                .is_some_and(|main| go_binaries(src_dir, &[main]) == [program])
+    }
+}
+
+// builds that are not skipped
+pub fn goreleaser_builds(src_dir: &Path) -> Vec<GoReleaserBuild> {
+    let Some(goreleaser) = load(src_dir) else {
+        return Vec::new();
+    };
+
+    goreleaser
+        .builds
+        .into_iter()
+        .filter(|build| !matches!(build.skip, Some(serde_yaml::Value::Bool(true))))
+        .collect()
+}
+
+// the build producing the main program, or the first build
+pub fn goreleaser_build(src_dir: &Path, main_program: Option<&str>) -> Option<GoReleaserBuild> {
+    let mut builds = goreleaser_builds(src_dir);
+    let i = main_program
+        .and_then(|program| {
+            builds
+                .iter()
+                .position(|build| build.builds_program(src_dir, program))
+        })
+        .unwrap_or_default();
+    (i < builds.len()).then(|| builds.swap_remove(i))
+}
+
+// the directories of the main packages built by goreleaser, relative to `src_dir`
+pub(super) fn goreleaser_mains(src_dir: &Path) -> Vec<String> {
+    let mut mains = Vec::new();
+    for main in goreleaser_builds(src_dir)
+        .iter()
+        .filter_map(GoReleaserBuild::main)
+    {
+        if !mains.contains(&main) {
+            mains.push(main);
+        }
+    }
+    mains
+}
+
+// e.g. ./cmd/foo/main.go -> cmd/foo, returns None for templates
+fn normalize_main(main: &str) -> Option<String> {
+    if main.contains("{{") {
+        return None;
+    }
+
+    let main = main.trim_end_matches('/');
+    let main = match main.rsplit_once('/') {
+        Some((dir, file)) if file.ends_with(".go") => dir,
+        None if main.ends_with(".go") => ".",
+        _ => main,
+    };
+    let main = main.trim_start_matches("./");
+    Some(if main.is_empty() {
+        ".".into()
+    } else {
+        main.into()
+    })
+}
+
+// environment variables that affect the build, e.g. CGO_ENABLED=0
+pub fn load_goreleaser_env(inputs: &mut AllInputs, build: &GoReleaserBuild) {
+    for (name, value) in build.env.iter().filter_map(|env| env.split_once('=')) {
+        if value.contains("{{") || matches!(name, "GO111MODULE" | "GOPROXY" | "GOSUMDB") {
+            continue;
+        }
+
+        let value = env_value(value);
+        inputs.add_reason(name, "goreleaser");
+        inputs
+            .env
+            .insert(name.to_owned().into(), (value.into(), Vec::new()));
+    }
 }
 
-pub fn write_ldflags(out: &mut impl Write, src_dir: &Path) -> Result<()> {
-    let (Some(raw), Some(re)) = (get_ldflags(src_dir), regex()) else {
+// quote the value as a nix string, goreleaser does not expand `${...}`
+fn env_value(value: &str) -> String {
+    if !value.is_empty() && value.bytes().all(|c| c.is_ascii_digit()) {
+        value.into()
+    } else {
+        format!("{value:?}").replace("${", "\\${")
+    }
+}
+
+pub fn write_build_flags(out: &mut impl Write, build: Option<&GoReleaserBuild>) -> Result<()> {
+    if let Some(build) = build {
+        let tags = build_tags(build);
+        if !tags.is_empty() {
+            write!(out, "  tags = [")?;
+            for tag in tags {
+                write!(out, " {tag:?}")?;
+            }
+            writeln!(out, " ];\n")?;
+        }
+    }
+
+    let (Some(raw), Some(re)) = (build.and_then(|build| build.ldflags.as_ref()), regex()) else {
         writeln!(out, "  ldflags = [ \"-s\" ];\n")?;
         return Ok(());
     };
 
     let mut raw = raw
-        .into_iter()
-        .flat_map(|ldflags| shlex::split(&parse_ldflags(&re, &ldflags)).unwrap_or_default());
+        .iter()
+        .flat_map(|ldflags| shlex::split(&parse_ldflags(&re, ldflags)).unwrap_or_default());
 
     let mut len = 0;
     let mut processed = Vec::new();
@@ -66,7 +187,30 @@ pub fn write_ldflags(out: &mut impl Write, src_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-fn get_ldflags(src_dir: &Path) -> Option<Vec<String>> {
+// `tags` along with the ones passed with `-tags` in `flags`
+fn build_tags(build: &GoReleaserBuild) -> Vec<&str> {
+    let mut flags = build.flags.iter();
+    let mut tags: Vec<_> = build.tags.iter().map(String::as_str).collect();
+    while let Some(flag) = flags.next() {
+        let value = match flag.strip_prefix("-tags") {
+            Some("") => flags.next().map(String::as_str),
+            Some(value) => value.strip_prefix('='),
+            None => None,
+        };
+        tags.extend(value.into_iter().flat_map(|value| value.split(',')));
+    }
+
+    let mut unique = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !tag.contains("{{") && !unique.contains(&tag) {
+            unique.push(tag);
+        }
+    }
+    unique
+}
+
+fn load(src_dir: &Path) -> Option<GoReleaser> {
     let names = [
         ".goreleaser.yml",
         ".goreleaser.yaml",
@@ -78,12 +222,7 @@ fn get_ldflags(src_dir: &Path) -> Option<Vec<String>> {
         .into_iter()
         .find_map(|name| File::open(src_dir.join(name)).ok())?;
 
-    serde_yaml::from_reader::<_, GoReleaser>(file)
-        .ok_inspect(|e| warn!("{e}"))?
-        .builds
-        .into_iter()
-        .next()?
-        .ldflags
+    serde_yaml::from_reader(file).ok_inspect(|e| warn!("{e}"))
 }
 
 fn regex() -> Option<Regex> {
@@ -111,7 +250,9 @@ fn parse_ldflags<'a>(re: &Regex, ldflags: &'a str) -> Cow<'a, str> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_ldflags, regex};
+    use indoc::indoc;
+
+    use super::{GoReleaserBuild, build_tags, env_value, normalize_main, parse_ldflags, regex};
 
     #[test]
     fn basic() {
@@ -134,4 +275,35 @@ mod tests {
             "-X main.Bad=${funcEnvUnknownVar} -s -w",
         );
     }
+
+    #[test]
+    fn mains() {
+        assert_eq!(normalize_main(".").unwrap(), ".");
+        assert_eq!(normalize_main("main.go").unwrap(), ".");
+        assert_eq!(normalize_main("./cmd/foo").unwrap(), "cmd/foo");
+        assert_eq!(normalize_main("./cmd/foo/").unwrap(), "cmd/foo");
+        assert_eq!(normalize_main("./cmd/foo/main.go").unwrap(), "cmd/foo");
+        assert_eq!(normalize_main("{{ .Env.MAIN }}"), None);
+    }
+
+    #[test]
+    fn tags() {
+        let build: GoReleaserBuild = serde_yaml::from_str(indoc! {"
+            flags:
+              - -trimpath
+              - -tags=netgo,osusergo
+              - -tags
+              - sqlite
+            tags: netgo
+        "})
+        .unwrap();
+        assert_eq!(build_tags(&build), ["netgo", "osusergo", "sqlite"]);
+    }
+
+    #[test]
+    fn env() {
+        assert_eq!(env_value("0"), "0");
+        assert_eq!(env_value("-mod=mod"), r#""-mod=mod""#);
+        assert_eq!(env_value("${HOME}/go"), r#""\${HOME}/go""#);
+    }
 }
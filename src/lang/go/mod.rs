@@ -1,16 +1,31 @@
+mod cgo;
 mod deps;
 mod goreleaser;
+mod toolchain;
 
 use std::{
-    fs::File,
+    collections::BTreeSet,
+    fs::{File, read_dir, read_to_string},
     io::{BufRead, BufReader},
+    path::Path,
     str::SplitWhitespace,
 };
 
+use regex::Regex;
 use semver::Version;
+use tracing::warn;
 
-pub use self::goreleaser::write_ldflags;
-use crate::{inputs::AllInputs, lang::go::deps::load_go_dependency};
+pub use self::{
+    cgo::{load_cgo_dependencies, scan_cgo},
+    goreleaser::{goreleaser_build, goreleaser_builds, load_goreleaser_env, write_build_flags},
+    toolchain::go_builder,
+};
+use crate::{
+    cfg::Mappings,
+    inputs::AllInputs,
+    lang::go::{deps::load_go_dependency, goreleaser::goreleaser_mains},
+    utils::ResultExt,
+};
 
 struct GoPackage<'a> {
     name: &'a str,
@@ -42,24 +57,186 @@ impl<'a> GoVersion<'a> {
     }
 }
 
-pub fn load_go_dependencies(inputs: &mut AllInputs, go_sum: &File) {
+pub fn load_go_dependencies(inputs: &mut AllInputs, mappings: &Mappings, go_sum: &File) {
     for line in BufReader::new(go_sum).lines().map_while(Result::ok) {
         if let Some(pkg) = GoPackage::from_line(line.split_whitespace()) {
+            let name = pkg.name;
             load_go_dependency(inputs, pkg);
+            if let Some(mapping) = mappings.go.get(name) {
+                mapping.apply(inputs, name);
+            }
         };
     }
 }
 
+pub struct GoMainPackages {
+    // every main package in the module, relative to `src_dir`
+    pub all: Vec<String>,
+    // the main packages that should be built and installed
+    pub selected: Vec<String>,
+}
+
+impl GoMainPackages {
+    // the value of subPackages, None if everything should be built
+    pub fn sub_packages(&self) -> Option<&[String]> {
+        (!self.selected.is_empty() && self.selected != self.all).then_some(&self.selected)
+    }
+}
+
+// prefers the packages built by goreleaser, then skips examples, tests and internal tools
+pub fn go_main_packages(src_dir: &Path) -> GoMainPackages {
+    let all = main_packages(src_dir);
+
+    let mains = goreleaser_mains(src_dir);
+    let selected = if mains.is_empty() {
+        all.iter()
+            .filter(|pkg| {
+                !pkg.split('/').any(|dir| {
+                    matches!(
+                        dir,
+                        "_examples"
+                            | "benchmark"
+                            | "benchmarks"
+                            | "contrib"
+                            | "demo"
+                            | "demos"
+                            | "doc"
+                            | "docs"
+                            | "example"
+                            | "examples"
+                            | "hack"
+                            | "integration"
+                            | "internal"
+                            | "script"
+                            | "scripts"
+                            | "test"
+                            | "tests"
+                            | "tools"
+                    )
+                })
+            })
+            .cloned()
+            .collect()
+    } else {
+        mains
+            .into_iter()
+            .filter(|main| all.is_empty() || all.contains(main))
+            .collect()
+    };
+
+    GoMainPackages { all, selected }
+}
+
+// names of the executables installed for the main packages
+pub fn go_binaries(src_dir: &Path, pkgs: &[String]) -> Vec<String> {
+    pkgs.iter()
+        .filter_map(|pkg| match pkg.rsplit_once('/') {
+            Some((_, name)) => Some(name.into()),
+            None if pkg == "." => module_name(src_dir),
+            None => Some(pkg.clone()),
+        })
+        .collect()
+}
+
+// the directories containing a main package, excluding vendored modules
+fn main_packages(src_dir: &Path) -> Vec<String> {
+    let (Some(main), Some(ignore)) = (
+        Regex::new(r"(?m)^package\s+main\s*$").ok_inspect(|e| warn!("{e}")),
+        Regex::new(r"(?m)^//\s*(?:go:build|\+build)\s+ignore\s*$").ok_inspect(|e| warn!("{e}")),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut pkgs = BTreeSet::new();
+    walk_go_files(src_dir, &mut |path| {
+        let Some(dir) = path.parent().and_then(|dir| dir.strip_prefix(src_dir).ok()) else {
+            return;
+        };
+        if dir.starts_with("vendor") {
+            return;
+        }
+        if let Ok(src) = read_to_string(path)
+            && main.is_match(&src)
+            && !ignore.is_match(&src)
+        {
+            let dir = dir.to_string_lossy();
+            pkgs.insert(if dir.is_empty() {
+                ".".into()
+            } else {
+                dir.replace('\\', "/")
+            });
+        }
+    });
+    pkgs.into_iter().collect()
+}
+
+// the last element of the module path, ignoring the major version suffix
+// e.g. github.com/foo/bar/v2 -> bar
+fn module_name(src_dir: &Path) -> Option<String> {
+    let go_mod = read_to_string(src_dir.join("go.mod")).ok()?;
+    let path = go_mod
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("module "))?
+        .trim()
+        .trim_matches('"');
+
+    let mut parts = path.rsplit('/');
+    let last = parts.next()?;
+    if last
+        .strip_prefix('v')
+        .is_some_and(|major| !major.is_empty() && major.bytes().all(|c| c.is_ascii_digit()))
+    {
+        parts.next()
+    } else {
+        Some(last)
+    }
+    .map(Into::into)
+}
+
+// calls `f` with every go source file that is part of the build, skipping tests,
+// hidden directories, directories ignored by the go tool, and nested modules
+fn walk_go_files(dir: &Path, f: &mut impl FnMut(&Path)) {
+    walk_module_files(dir, false, f);
+}
+
+fn walk_module_files(dir: &Path, vendor: bool, f: &mut impl FnMut(&Path)) {
+    let Ok(entries) = read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        match entry.file_type() {
+            Ok(ty) if ty.is_dir() && !name.starts_with(['.', '_']) && name != "testdata" => {
+                let path = entry.path();
+                // go.mod files of vendored modules are ignored
+                if vendor || name == "vendor" || !path.join("go.mod").is_file() {
+                    walk_module_files(&path, vendor || name == "vendor", f);
+                }
+            }
+            Ok(ty) if ty.is_file() && name.ends_with(".go") && !name.ends_with("_test.go") => {
+                f(&entry.path());
+            }
+            _ => {}
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::fs::File;
+    use std::fs::{File, create_dir_all, write};
 
     use insta::assert_debug_snapshot;
     use semver::Version;
+    use tempfile::tempdir;
 
     use crate::{
+        cfg::Mappings,
         inputs::AllInputs,
-        lang::go::{GoPackage, load_go_dependencies},
+        lang::go::{GoPackage, go_binaries, go_main_packages, load_go_dependencies},
     };
 
     #[test]
@@ -67,6 +244,7 @@ mod tests {
         let mut inputs = AllInputs::default();
         load_go_dependencies(
             &mut inputs,
+            &Mappings::default(),
             &File::open("src/lang/go/fixtures/basic/go.sum").unwrap(),
         );
         assert_debug_snapshot!(inputs);
@@ -85,6 +263,59 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn main_packages() {
+        let dir = tempdir().unwrap();
+        let src = dir.path();
+        for dir in [
+            "cmd/foo",
+            "cmd/bar",
+            "examples/hello",
+            "internal/gen",
+            "tools/lint",
+            "vendor/x/cmd",
+        ] {
+            create_dir_all(src.join(dir)).unwrap();
+        }
+        write(
+            src.join("go.mod"),
+            "module github.com/owner/repo/v2\n\ngo 1.22\n",
+        )
+        .unwrap();
+        write(src.join("main.go"), "package main\n").unwrap();
+        write(src.join("cmd/foo/main.go"), "package main\n").unwrap();
+        write(
+            src.join("cmd/bar/main.go"),
+            "// Command bar\npackage main\n",
+        )
+        .unwrap();
+        write(src.join("cmd/bar/bar_test.go"), "package main\n").unwrap();
+        write(src.join("examples/hello/main.go"), "package main\n").unwrap();
+        write(
+            src.join("internal/gen/gen.go"),
+            "//go:build ignore\n\npackage main\n",
+        )
+        .unwrap();
+        write(src.join("vendor/x/cmd/main.go"), "package main\n").unwrap();
+        // a separate module
+        write(src.join("tools/lint/go.mod"), "module lint\n").unwrap();
+        write(src.join("tools/lint/main.go"), "package main\n").unwrap();
+
+        let pkgs = go_main_packages(src);
+        assert_eq!(pkgs.all, [".", "cmd/bar", "cmd/foo", "examples/hello"]);
+        assert_eq!(pkgs.sub_packages().unwrap(), [".", "cmd/bar", "cmd/foo"]);
+        assert_eq!(go_binaries(src, &pkgs.selected), ["repo", "bar", "foo"]);
+
+        write(
+            src.join(".goreleaser.yaml"),
+            "builds:\n  - main: ./cmd/foo\n    binary: foo\n",
+        )
+        .unwrap();
+        let pkgs = go_main_packages(src);
+        assert_eq!(pkgs.sub_packages().unwrap(), ["cmd/foo"]);
+        assert_eq!(go_binaries(src, &pkgs.selected), ["foo"]);
+    }
+
     fn parse_line(line: &str) -> (&str, Version) {
         let pkg = GoPackage::from_line(line.split_whitespace()).unwrap();
         (pkg.name, pkg.version.get().unwrap())
@@ -0,0 +1,159 @@
+use std::{collections::BTreeMap, fs::read_to_string, path::Path};
+
+use serde::Deserialize;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::{
+    cmd::NIX,
+    utils::{CommandExt, ResultExt},
+};
+
+#[derive(Deserialize)]
+struct GoToolchains {
+    // version of the default go
+    go: String,
+    // versions of go used by the versioned builders, e.g. buildGo124Module
+    builders: BTreeMap<String, String>,
+}
+
+// the minor version of the versioned builder to use when the default go is too old for go.mod
+pub async fn go_builder(src_dir: &Path, nixpkgs: &str) -> Option<u32> {
+    let go_mod = read_to_string(src_dir.join("go.mod")).ok()?;
+    let (required, toolchain) = parse_go_mod(&go_mod);
+    let required = required?;
+
+    let stdout = Command::new(NIX)
+        .arg("eval")
+        .arg("--extra-experimental-features")
+        .arg("nix-command")
+        .arg("--impure")
+        .arg("--json")
+        .arg("--expr")
+        .arg(format!(
+            r#"let pkgs=import({nixpkgs}){{}};lib=pkgs.lib;in{{go=pkgs.go.version;builders=lib.filterAttrs(_:v:v!=null)(lib.genAttrs(builtins.filter(n:builtins.match"buildGo1[0-9]+Module"n!=null)(builtins.attrNames pkgs))(n:let v=builtins.tryEval(pkgs."go_1_${{lib.removeSuffix"Module"(lib.removePrefix"buildGo1"n)}}".version or null);in if v.success then v.value else null));}}"#,
+        ))
+        .get_stdout()
+        .await
+        .ok_inspect(|e| warn!("failed to find go toolchains: {e}"))?;
+    let toolchains: GoToolchains = serde_json::from_slice(&stdout).ok_inspect(|e| warn!("{e}"))?;
+
+    let minor = select_builder(required, toolchain, &toolchains)?;
+    info!("go.mod requires go {required:?}, using buildGo1{minor}Module");
+    Some(minor)
+}
+
+// the `go` and `toolchain` directives
+fn parse_go_mod(go_mod: &str) -> (Option<[u32; 3]>, Option<[u32; 3]>) {
+    let mut go = None;
+    let mut toolchain = None;
+    for line in go_mod.lines() {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("go"), Some(version)) => go = parse_version(version),
+            (Some("toolchain"), Some(version)) => {
+                toolchain = version.strip_prefix("go").and_then(parse_version);
+            }
+            _ => {}
+        }
+    }
+    (go, toolchain)
+}
+
+// e.g. 1.24, 1.24.1, or 1.25rc1
+fn parse_version(version: &str) -> Option<[u32; 3]> {
+    let mut version = version.split('.').map(|part| {
+        part.bytes()
+            .take_while(u8::is_ascii_digit)
+            .fold(0, |n, c| n * 10 + u32::from(c - b'0'))
+    });
+    Some([
+        version.next()?,
+        version.next()?,
+        version.next().unwrap_or(0),
+    ])
+}
+
+// prefers the toolchain in go.mod, then the oldest go that is new enough
+fn select_builder(
+    required: [u32; 3],
+    toolchain: Option<[u32; 3]>,
+    toolchains: &GoToolchains,
+) -> Option<u32> {
+    if parse_version(&toolchains.go).is_some_and(|go| go >= required) {
+        return None;
+    }
+
+    let candidates: BTreeMap<_, _> = toolchains
+        .builders
+        .iter()
+        .filter_map(|(name, version)| {
+            let minor = name
+                .strip_prefix("buildGo1")?
+                .strip_suffix("Module")?
+                .parse()
+                .ok()?;
+            let version = parse_version(version)?;
+            (version >= required).then_some((version, minor))
+        })
+        .collect();
+
+    toolchain
+        .and_then(|toolchain| {
+            candidates
+                .iter()
+                .find(|([major, minor, _], _)| [*major, *minor] == [toolchain[0], toolchain[1]])
+        })
+        .or_else(|| candidates.first_key_value())
+        .map(|(_, minor)| *minor)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use indoc::indoc;
+
+    use super::{GoToolchains, parse_go_mod, select_builder};
+
+    #[test]
+    fn directives() {
+        assert_eq!(
+            parse_go_mod(indoc! {"
+                module github.com/owner/repo
+
+                go 1.24.2
+
+                toolchain go1.25.0
+
+                require golang.org/x/mod v0.11.0
+            "}),
+            (Some([1, 24, 2]), Some([1, 25, 0])),
+        );
+        assert_eq!(parse_go_mod("go 1.21rc1\n"), (Some([1, 21, 0]), None));
+        assert_eq!(parse_go_mod("module foo\n"), (None, None));
+    }
+
+    #[test]
+    fn builders() {
+        let toolchains = GoToolchains {
+            go: "1.24.5".into(),
+            builders: BTreeMap::from_iter(
+                [
+                    ("buildGo124Module", "1.24.5"),
+                    ("buildGo125Module", "1.25.1"),
+                    ("buildGo126Module", "1.26rc2"),
+                ]
+                .map(|(name, version)| (name.into(), version.into())),
+            ),
+        };
+
+        assert_eq!(select_builder([1, 24, 0], None, &toolchains), None);
+        assert_eq!(select_builder([1, 25, 0], None, &toolchains), Some(25));
+        assert_eq!(
+            select_builder([1, 25, 0], Some([1, 26, 0]), &toolchains),
+            Some(26),
+        );
+        assert_eq!(select_builder([1, 27, 0], None, &toolchains), None);
+    }
+}
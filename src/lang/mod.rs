@@ -1,4 +1,6 @@
 pub mod go;
 pub mod npm;
+pub mod pkg_config;
+pub mod pypi;
 pub mod python;
 pub mod rust;
@@ -0,0 +1,41 @@
+use crate::{inputs::AllInputs, macros::input_macros};
+
+// native modules, built with node-gyp unless prebuilt binaries can be downloaded
+pub(super) fn load_npm_dependency(inputs: &mut AllInputs, name: &str) {
+    input_macros!(inputs, name);
+
+    match name {
+        // keep-sorted start group_start_regex=^\s*"
+        "@serialport/bindings-cpp" | "serialport" => {
+            native_build!("python3");
+            build!("udev"; linux);
+        }
+        "argon2" | "bcrypt" | "node-gyp" | "node-pty" => native_build!("python3"),
+        "better-sqlite3" | "sqlite3" => {
+            native_build!("python3");
+            build!("sqlite");
+        }
+        "canvas" => {
+            native_build!("pkg-config", "python3");
+            build!("cairo", "giflib", "libjpeg", "librsvg", "pango", "pixman");
+        }
+        "keytar" => {
+            native_build!("pkg-config", "python3");
+            build!("libsecret"; linux);
+        }
+        "sharp" => {
+            native_build!("pkg-config", "python3");
+            build!("vips");
+        }
+        "usb" => {
+            native_build!("python3");
+            build!("libusb1");
+        }
+        "zeromq" => {
+            native_build!("pkg-config", "python3");
+            build!("zeromq");
+        }
+        // keep-sorted end
+        _ => {}
+    }
+}
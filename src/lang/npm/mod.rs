@@ -1,14 +1,38 @@
-use std::{fs::read_to_string, path::Path};
+mod deps;
+mod node;
 
-use serde::Deserialize;
+use std::{collections::BTreeMap, fs::read_to_string, path::Path};
+
+use serde::{Deserialize, de::IgnoredAny};
 use tracing::warn;
 
-use crate::utils::ResultExt;
+use crate::{
+    cfg::Mappings,
+    cli::PackageManager,
+    inputs::AllInputs,
+    lang::npm::{deps::load_npm_dependency, node::nodejs_major},
+    macros::input_macros,
+    utils::ResultExt,
+};
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct PackageJson {
+    #[serde(default)]
+    name: String,
+    bin: Option<Bin>,
     #[serde(default)]
     scripts: Scripts,
+    #[serde(default)]
+    dependencies: BTreeMap<String, IgnoredAny>,
+    #[serde(default)]
+    dev_dependencies: BTreeMap<String, IgnoredAny>,
+    #[serde(default)]
+    optional_dependencies: BTreeMap<String, IgnoredAny>,
+    #[serde(default)]
+    engines: Engines,
+    // whether node-gyp builds binding.gyp, defaults to whether it exists
+    gypfile: Option<bool>,
 }
 
 #[derive(Default, Deserialize)]
@@ -16,6 +40,52 @@ struct Scripts {
     build: Option<String>,
 }
 
+#[derive(Default, Deserialize)]
+struct Engines {
+    node: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Bin {
+    Multiple(BTreeMap<String, IgnoredAny>),
+    // named after the package
+    Single(IgnoredAny),
+}
+
+impl PackageManager {
+    pub fn detect(src_dir: &Path) -> Option<Self> {
+        if src_dir.join("pnpm-lock.yaml").is_file() {
+            Some(Self::Pnpm)
+        } else if let Ok(yarn_lock) = read_to_string(src_dir.join("yarn.lock")) {
+            // yarn berry lockfiles are yaml with a metadata section
+            Some(
+                if yarn_lock
+                    .lines()
+                    .any(|line| line.starts_with("__metadata:"))
+                {
+                    Self::YarnBerry
+                } else {
+                    Self::Yarn
+                },
+            )
+        } else if src_dir.join("bun.lock").is_file() {
+            Some(Self::Bun)
+        } else {
+            None
+        }
+    }
+
+    // the command running a script from package.json
+    pub fn run(self) -> &'static str {
+        match self {
+            Self::Bun => "bun run",
+            Self::Pnpm => "pnpm run",
+            Self::Yarn | Self::YarnBerry => "yarn run",
+        }
+    }
+}
+
 // assumes a build script exists when package.json can't be read or parsed
 pub fn npm_has_build_script(src_dir: &Path) -> bool {
     let Some(package_json) =
@@ -32,3 +102,170 @@ pub fn npm_has_build_script(src_dir: &Path) -> bool {
         }
     }
 }
+
+// names of the executables in the bin field
+pub fn npm_bins(src_dir: &Path) -> Vec<String> {
+    let Some(package) = read_package_json(src_dir) else {
+        return Vec::new();
+    };
+
+    match package.bin {
+        Some(Bin::Single(_)) => {
+            // scoped packages are named after the package without the scope
+            let name = package
+                .name
+                .rsplit_once('/')
+                .map_or(package.name.as_str(), |(_, name)| name);
+            if name.is_empty() {
+                Vec::new()
+            } else {
+                vec![name.into()]
+            }
+        }
+        Some(Bin::Multiple(bins)) => bins.into_keys().collect(),
+        None => Vec::new(),
+    }
+}
+
+pub fn load_npm_dependencies(inputs: &mut AllInputs, mappings: &Mappings, src_dir: &Path) {
+    let Some(package) = read_package_json(src_dir) else {
+        return;
+    };
+
+    if package
+        .gypfile
+        .unwrap_or_else(|| src_dir.join("binding.gyp").is_file())
+    {
+        input_macros!(inputs, "binding.gyp");
+        native_build!("python3");
+    }
+
+    for name in package
+        .dependencies
+        .keys()
+        .chain(package.dev_dependencies.keys())
+        .chain(package.optional_dependencies.keys())
+    {
+        load_npm_dependency(inputs, name);
+        if let Some(mapping) = mappings.npm.get(name) {
+            mapping.apply(inputs, name);
+        }
+    }
+}
+
+// the major version of nodejs to use instead of the default one, based on engines.node
+pub async fn npm_nodejs_major(src_dir: &Path, nixpkgs: &str) -> Option<u32> {
+    let engine = read_package_json(src_dir)?.engines.node?;
+    nodejs_major(&engine, nixpkgs).await
+}
+
+fn read_package_json(src_dir: &Path) -> Option<PackageJson> {
+    read_to_string(src_dir.join("package.json"))
+        .ok_inspect(|e| warn!("{e}"))
+        .and_then(|package_json| serde_json::from_str(&package_json).ok_inspect(|e| warn!("{e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use indoc::indoc;
+    use tempfile::tempdir;
+
+    use super::{PackageManager, load_npm_dependencies, npm_bins};
+    use crate::{cfg::Mappings, inputs::AllInputs};
+
+    #[test]
+    fn bins() {
+        let dir = tempdir().unwrap();
+        let package_json = dir.path().join("package.json");
+
+        write(
+            &package_json,
+            r#"{ "name": "@scope/foo", "bin": "cli.js" }"#,
+        )
+        .unwrap();
+        assert_eq!(npm_bins(dir.path()), ["foo"]);
+
+        write(
+            &package_json,
+            r#"{ "name": "foo", "bin": { "foo-server": "server.js", "foo": "cli.js" } }"#,
+        )
+        .unwrap();
+        assert_eq!(npm_bins(dir.path()), ["foo", "foo-server"]);
+
+        write(&package_json, r#"{ "name": "foo" }"#).unwrap();
+        assert!(npm_bins(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn package_managers() {
+        let dir = tempdir().unwrap();
+        let src = dir.path();
+        assert_eq!(PackageManager::detect(src), None);
+
+        write(src.join("bun.lock"), "{}").unwrap();
+        assert_eq!(PackageManager::detect(src), Some(PackageManager::Bun));
+
+        write(
+            src.join("yarn.lock"),
+            indoc! {r#"
+                # THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+                # yarn lockfile v1
+
+
+                foo@^1.0.0:
+                  version "1.0.0"
+            "#},
+        )
+        .unwrap();
+        assert_eq!(PackageManager::detect(src), Some(PackageManager::Yarn));
+
+        write(
+            src.join("yarn.lock"),
+            indoc! {r#"
+                # This file is generated by running "yarn install" inside your project.
+
+                __metadata:
+                  version: 8
+                  cacheKey: 10c0
+            "#},
+        )
+        .unwrap();
+        assert_eq!(PackageManager::detect(src), Some(PackageManager::YarnBerry));
+
+        write(src.join("pnpm-lock.yaml"), "lockfileVersion: '9.0'\n").unwrap();
+        assert_eq!(PackageManager::detect(src), Some(PackageManager::Pnpm));
+    }
+
+    #[test]
+    fn native_modules() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path().join("package.json"),
+            indoc! {r#"
+                {
+                  "name": "foo",
+                  "gypfile": true,
+                  "dependencies": {
+                    "keytar": "^7.9.0",
+                    "left-pad": "^1.3.0"
+                  },
+                  "optionalDependencies": {
+                    "sharp": "^0.34.0"
+                  }
+                }
+            "#},
+        )
+        .unwrap();
+
+        let mut inputs = AllInputs::default();
+        load_npm_dependencies(&mut inputs, &Mappings::default(), dir.path());
+        assert_eq!(
+            Vec::from_iter(&inputs.native_build_inputs.always),
+            ["pkg-config", "python3"],
+        );
+        assert_eq!(Vec::from_iter(&inputs.build_inputs.always), ["vips"]);
+        assert_eq!(Vec::from_iter(&inputs.build_inputs.linux), ["libsecret"]);
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::{
+    cmd::NIX,
+    utils::{CommandExt, ResultExt},
+};
+
+#[derive(Deserialize)]
+struct NodeVersions {
+    // version of the default nodejs
+    nodejs: String,
+    // versions of the versioned attributes, e.g. nodejs_22
+    versions: BTreeMap<String, String>,
+}
+
+// the major version of nodejs_XX to use when the default nodejs doesn't satisfy engines.node
+pub(super) async fn nodejs_major(engine: &str, nixpkgs: &str) -> Option<u32> {
+    let ranges = parse_engine(engine);
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let stdout = Command::new(NIX)
+        .arg("eval")
+        .arg("--extra-experimental-features")
+        .arg("nix-command")
+        .arg("--impure")
+        .arg("--json")
+        .arg("--expr")
+        .arg(format!(
+            r#"let pkgs=import({nixpkgs}){{}};lib=pkgs.lib;in{{nodejs=pkgs.nodejs.version;versions=lib.filterAttrs(_:v:v!=null)(lib.genAttrs(builtins.filter(n:builtins.match"nodejs_[0-9]+"n!=null)(builtins.attrNames pkgs))(n:let v=builtins.tryEval(pkgs.${{n}}.version or null);in if v.success then v.value else null));}}"#,
+        ))
+        .get_stdout()
+        .await
+        .ok_inspect(|e| warn!("failed to find nodejs versions: {e}"))?;
+    let versions: NodeVersions = serde_json::from_slice(&stdout).ok_inspect(|e| warn!("{e}"))?;
+
+    let major = select_nodejs(&ranges, &versions)?;
+    info!("engines.node requires {engine}, using nodejs_{major}");
+    Some(major)
+}
+
+// e.g. `>=18`, `^18.17.0 || >=20.3.0`, `>= 20 < 23`, or `18 - 20.x`
+fn parse_engine(engine: &str) -> Vec<VersionReq> {
+    engine
+        .split("||")
+        .filter_map(|range| {
+            // hyphen ranges are inclusive on both ends
+            if let Some((low, high)) = range.split_once(" - ") {
+                let version = |version: &str| version.trim().trim_start_matches('v').to_owned();
+                return VersionReq::parse(&format!(">={}, <={}", version(low), version(high)))
+                    .ok_inspect(|e| warn!("{e}"));
+            }
+
+            let mut comparators = Vec::new();
+            let mut op = String::new();
+            for token in range.split_whitespace() {
+                if token.bytes().all(|c| b"<>=^~".contains(&c)) {
+                    op.push_str(token);
+                } else {
+                    comparators.push(format!("{op}{}", token.trim_start_matches('v')));
+                    op.clear();
+                }
+            }
+            if comparators.is_empty() {
+                return None;
+            }
+            VersionReq::parse(&comparators.join(", ")).ok_inspect(|e| warn!("{e}"))
+        })
+        .collect()
+}
+
+// prefers the oldest version satisfying the ranges
+fn select_nodejs(ranges: &[VersionReq], versions: &NodeVersions) -> Option<u32> {
+    let matches = |version: &str| {
+        Version::parse(version).is_ok_and(|version| ranges.iter().any(|req| req.matches(&version)))
+    };
+    if matches(&versions.nodejs) {
+        return None;
+    }
+
+    versions
+        .versions
+        .iter()
+        .filter(|(_, version)| matches(version))
+        .filter_map(|(name, _)| name.strip_prefix("nodejs_")?.parse().ok())
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{NodeVersions, parse_engine, select_nodejs};
+
+    #[test]
+    fn engines() {
+        let versions = NodeVersions {
+            nodejs: "22.20.0".into(),
+            versions: BTreeMap::from_iter(
+                [
+                    ("nodejs_20", "20.19.5"),
+                    ("nodejs_22", "22.20.0"),
+                    ("nodejs_24", "24.9.0"),
+                ]
+                .map(|(name, version)| (name.into(), version.into())),
+            ),
+        };
+        let select = |engine| select_nodejs(&parse_engine(engine), &versions);
+
+        assert_eq!(select(">=18"), None);
+        assert_eq!(select(">= 24"), Some(24));
+        assert_eq!(select("^18.17.0 || ^20.3.0"), Some(20));
+        assert_eq!(select(">=20 <22"), Some(20));
+        assert_eq!(select("20.x || >=24"), Some(20));
+        assert_eq!(select("v26"), None);
+        assert_eq!(select("18.0.0 - 20.x"), Some(20));
+        assert_eq!(select("24 - 26"), Some(24));
+        assert_eq!(parse_engine("18.0.0 - 20.x").len(), 1);
+        assert_eq!(parse_engine("*").len(), 1);
+        assert!(parse_engine("").is_empty());
+    }
+}
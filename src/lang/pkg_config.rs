@@ -0,0 +1,175 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, create_dir_all},
+};
+
+use rustc_hash::FxHashMap;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+use crate::{
+    cmd::NIX,
+    utils::{CommandExt, ResultExt, nixpkgs_cache_path},
+};
+
+// maps pkg-config modules to the attributes in nixpkgs providing them
+pub struct PkgConfigIndex(FxHashMap<String, String>);
+
+impl PkgConfigIndex {
+    // the index is cached on disk for nixpkgs in the nix store
+    pub async fn load(nixpkgs: &str) -> Option<Self> {
+        let cache = nixpkgs_cache_path(nixpkgs, "pkg-config-index").await;
+        if let Some(cache) = &cache
+            && let Ok(file) = File::open(cache)
+            && let Some(index) = serde_json::from_reader(file).ok_inspect(|e| warn!("{e}"))
+        {
+            debug!("loaded pkg-config index from {}", cache.display());
+            return Some(Self(index));
+        }
+
+        let stdout = Command::new(NIX)
+            .arg("eval")
+            .arg("--extra-experimental-features")
+            .arg("nix-command")
+            .arg("--impure")
+            .arg("--json")
+            .arg("--expr")
+            .arg(format!(
+                r#"let pkgs=import({nixpkgs}){{}};lib=pkgs.lib;in lib.concatMapAttrs(attr:drv:let modules=builtins.tryEval(let x=if lib.isDerivation drv then drv.meta.pkgConfigModules or [] else [];in builtins.deepSeq x x);in lib.optionalAttrs(modules.success&&builtins.isList modules.value&&modules.value!=[]){{${{attr}}=modules.value;}})pkgs"#,
+            ))
+            .get_stdout()
+            .await
+            .ok_inspect(|e| warn!("failed to index pkg-config modules: {e}"))?;
+
+        let index =
+            Self::from_modules(serde_json::from_slice(&stdout).ok_inspect(|e| warn!("{e}"))?);
+
+        if let Some(cache) = cache
+            && let Some(file) = cache
+                .parent()
+                .map_or(Ok(()), create_dir_all)
+                .and_then(|_| File::create(&cache))
+                .ok_inspect(|e| warn!("{e}"))
+        {
+            serde_json::to_writer(file, &index.0).ok_inspect(|e| warn!("{e}"));
+        }
+
+        Some(index)
+    }
+
+    fn from_modules(modules: BTreeMap<String, Vec<String>>) -> Self {
+        let mut index: FxHashMap<String, String> = FxHashMap::default();
+        for (attr, modules) in modules {
+            for module in modules {
+                // prefer the shortest attribute, e.g. openssl over openssl_3_5
+                match index.get(&module) {
+                    Some(prev) if prev.len() <= attr.len() => {}
+                    _ => {
+                        index.insert(module, attr.clone());
+                    }
+                }
+            }
+        }
+        Self(index)
+    }
+
+    // falls back to guessing the attribute from the name of the module
+    pub fn get(&self, module: &str) -> String {
+        self.0
+            .get(module)
+            .cloned()
+            .unwrap_or_else(|| pkg_config_input(module))
+    }
+}
+
+pub fn pkg_config_input(name: &str) -> String {
+    match name {
+        // keep-sorted start
+        "alsa" => "alsa-lib",
+        "dbus-1" => "dbus",
+        "gtk+-3.0" => "gtk3",
+        "libcrypto" | "libssl" => "openssl",
+        "libpipewire-0.3" => "pipewire",
+        "libpulse" => "libpulseaudio",
+        "libudev" => "udev",
+        "libxml-2.0" => "libxml2",
+        "sqlite3" => "sqlite",
+        "wayland-client" | "wayland-server" => "wayland",
+        "x11" => "xorg.libX11",
+        "xkbcommon" => "libxkbcommon",
+        // keep-sorted end
+        name => {
+            // strip api versions such as glib-2.0
+            return name
+                .rsplit_once('-')
+                .filter(|(_, version)| version.starts_with(|c: char| c.is_ascii_digit()))
+                .map_or(name, |(name, _)| name)
+                .into();
+        }
+    }
+    .into()
+}
+
+// libraries passed to the linker with -l, returns None for the ones provided by the toolchain
+pub fn library_input(name: &str) -> Option<&str> {
+    Some(match name {
+        // provided by the libc or the compiler
+        "c" | "dl" | "m" | "pthread" | "rt" | "stdc++" => return None,
+        // keep-sorted start
+        "bz2" => "bzip2",
+        "crypto" | "ssl" => "openssl",
+        "ffi" => "libffi",
+        "git2" => "libgit2",
+        "jpeg" => "libjpeg",
+        "lzma" => "xz",
+        "png" | "png16" => "libpng",
+        "pq" => "libpq",
+        "sodium" => "libsodium",
+        "sqlite3" => "sqlite",
+        "usb-1.0" => "libusb1",
+        "uv" => "libuv",
+        "xml2" => "libxml2",
+        "xslt" => "libxslt",
+        "yaml" => "libyaml",
+        "z" => "zlib",
+        // keep-sorted end
+        name => name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{PkgConfigIndex, library_input, pkg_config_input};
+
+    #[test]
+    fn modules() {
+        let index = PkgConfigIndex::from_modules(BTreeMap::from_iter(
+            [
+                ("libsecret", vec!["libsecret-1"]),
+                ("openssl", vec!["libcrypto", "libssl", "openssl"]),
+                ("openssl_3_5", vec!["libcrypto", "libssl", "openssl"]),
+            ]
+            .map(|(attr, modules)| (attr.into(), modules.into_iter().map(Into::into).collect())),
+        ));
+
+        assert_eq!(index.get("libsecret-1"), "libsecret");
+        assert_eq!(index.get("libssl"), "openssl");
+        assert_eq!(index.get("glib-2.0"), "glib");
+    }
+
+    #[test]
+    fn pkg_config() {
+        assert_eq!(pkg_config_input("glib-2.0"), "glib");
+        assert_eq!(pkg_config_input("libudev"), "udev");
+        assert_eq!(pkg_config_input("gtk4"), "gtk4");
+    }
+
+    #[test]
+    fn libraries() {
+        assert_eq!(library_input("m"), None);
+        assert_eq!(library_input("z"), Some("zlib"));
+        assert_eq!(library_input("X11"), Some("X11"));
+    }
+}
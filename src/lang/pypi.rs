@@ -0,0 +1,164 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, create_dir_all},
+};
+
+use rustc_hash::FxHashMap;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+use crate::{
+    cmd::NIX,
+    lang::python::PythonDependencies,
+    utils::{CommandExt, ResultExt, nixpkgs_cache_path},
+};
+
+// maps normalized python distribution names to attributes in python3Packages
+pub struct PypiIndex(FxHashMap<String, String>);
+
+impl PypiIndex {
+    // the index is cached on disk for nixpkgs in the nix store
+    pub async fn load(nixpkgs: &str) -> Option<Self> {
+        let cache = nixpkgs_cache_path(nixpkgs, "pypi-index").await;
+        if let Some(cache) = &cache
+            && let Ok(file) = File::open(cache)
+            && let Some(index) = serde_json::from_reader(file).ok_inspect(|e| warn!("{e}"))
+        {
+            debug!("loaded python package index from {}", cache.display());
+            return Some(Self(index));
+        }
+
+        let stdout = Command::new(NIX)
+            .arg("eval")
+            .arg("--extra-experimental-features")
+            .arg("nix-command")
+            .arg("--impure")
+            .arg("--json")
+            .arg("--expr")
+            .arg(format!(
+                r#"let pkgs=import({nixpkgs}){{}};lib=pkgs.lib;in lib.concatMapAttrs(attr:drv:let pname=builtins.tryEval(if builtins.isAttrs drv then drv.pname or null else null);in lib.optionalAttrs(pname.success&&builtins.isString pname.value){{${{attr}}=pname.value;}})pkgs.python3Packages"#,
+            ))
+            .get_stdout()
+            .await
+            .ok_inspect(|e| warn!("failed to index python packages: {e}"))?;
+
+        let index =
+            Self::from_pnames(serde_json::from_slice(&stdout).ok_inspect(|e| warn!("{e}"))?);
+
+        if let Some(cache) = cache
+            && let Some(file) = cache
+                .parent()
+                .map_or(Ok(()), create_dir_all)
+                .and_then(|_| File::create(&cache))
+                .ok_inspect(|e| warn!("{e}"))
+        {
+            serde_json::to_writer(file, &index.0).ok_inspect(|e| warn!("{e}"));
+        }
+
+        Some(index)
+    }
+
+    fn from_pnames(pnames: BTreeMap<String, String>) -> Self {
+        let mut index: FxHashMap<String, String> = FxHashMap::default();
+        for (attr, pname) in pnames {
+            let name = normalize(&pname);
+            // prefer the attribute named after the package, then the shortest one
+            // e.g. protobuf over protobuf5
+            match index.get(&name) {
+                _ if attr == name => {
+                    index.insert(name, attr);
+                }
+                Some(prev) if prev == &name || prev.len() <= attr.len() => {}
+                _ => {
+                    index.insert(name, attr);
+                }
+            }
+        }
+        Self(index)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(&normalize(name)).map(String::as_str)
+    }
+
+    pub fn resolve(&self, deps: &mut PythonDependencies) {
+        let renames: Vec<_> = deps
+            .names()
+            .filter_map(|name| {
+                let attr = self.get(name)?;
+                (attr != name).then(|| (name.clone(), attr.to_owned()))
+            })
+            .collect();
+
+        for (old, new) in renames {
+            deps.rename(&old, &new);
+        }
+    }
+}
+
+// https://packaging.python.org/en/latest/specifications/name-normalization
+fn normalize(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{PypiIndex, normalize};
+    use crate::lang::python::get_python_dependencies;
+
+    #[test]
+    fn normalized() {
+        assert_eq!(normalize("PyYAML"), "pyyaml");
+        assert_eq!(normalize("zope.interface"), "zope-interface");
+        assert_eq!(normalize("typing__extensions"), "typing-extensions");
+    }
+
+    #[test]
+    fn resolve() {
+        let index = PypiIndex::from_pnames(BTreeMap::from_iter(
+            [
+                ("pillow", "pillow"),
+                ("protobuf", "protobuf"),
+                ("protobuf5", "protobuf"),
+                ("pyqt", "PyQt5"),
+                ("pyqt5", "PyQt5"),
+                ("pyyaml", "PyYAML"),
+                ("zope-interface", "zope.interface"),
+            ]
+            .map(|(attr, pname)| (attr.into(), pname.into())),
+        ));
+
+        let mut deps = get_python_dependencies([
+            "Pillow>=10",
+            "protobuf",
+            "PyQt5",
+            "PyYAML",
+            "zope.interface",
+            "unknown",
+        ]);
+        index.resolve(&mut deps);
+        assert_eq!(
+            Vec::from_iter(deps.always),
+            [
+                "pillow",
+                "protobuf",
+                "pyqt5",
+                "pyyaml",
+                "unknown",
+                "zope-interface",
+            ],
+        );
+    }
+}
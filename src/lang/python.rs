@@ -1,40 +1,83 @@
 use std::{
+    cmp::Ordering,
     collections::{BTreeMap, BTreeSet},
     fs::{self, File},
     io::{BufRead, BufReader},
+    iter::repeat,
     mem,
     path::{Path, PathBuf},
 };
 
 use chumsky::{Parser, error::EmptyErr, extra::Err, primitive::end};
-use heck::{AsKebabCase, ToKebabCase};
-use pep_508::{Comparator, Dependency, Marker, Operator, Variable};
-use serde::Deserialize;
+use heck::ToKebabCase;
+use itertools::Itertools;
+use pep_508::{Comparator, Dependency, Marker, Operator, Spec, Variable};
+use regex::Regex;
+use serde::{Deserialize, Serialize, de::IgnoredAny};
 use serde_with::{DefaultOnError, serde_as};
 use tracing::warn;
 
-use crate::{inputs::AllInputs, license::parse_spdx_expression, utils::ResultExt};
+use crate::{
+    cfg::Mappings,
+    inputs::AllInputs,
+    lang::pkg_config::{library_input, pkg_config_input},
+    license::parse_spdx_expression,
+    macros::input_macros,
+    utils::ResultExt,
+};
 
-#[derive(Default)]
+#[derive(Clone, Default, Serialize)]
 pub struct PythonDependencies {
     pub always: BTreeSet<String>,
     pub optional: BTreeMap<String, BTreeSet<String>>,
+    // PEP 735 dependency groups and their tool-specific equivalents, only used for check inputs
+    pub groups: BTreeMap<String, BTreeSet<String>>,
+    // nativeCheckInputs
+    pub check: BTreeSet<String>,
+    // requirements to put in pythonRelaxDeps
+    pub relax: BTreeSet<String>,
+    // requirements to put in pythonRemoveDeps
+    pub remove: BTreeSet<String>,
+    #[serde(skip)]
+    constraints: BTreeMap<String, Constraint>,
+}
+
+#[derive(Clone)]
+struct Constraint {
+    // the name of the requirement as it appears in the package metadata
+    name: String,
+    specs: Vec<(Comparator, String)>,
 }
 
+#[serde_as]
 #[derive(Default, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
 pub struct Pyproject {
     build_system: BuildSystem,
     project: Project,
     tool: Tool,
+    #[serde_as(as = "DefaultOnError")]
+    dependency_groups: BTreeMap<String, Vec<GroupEntry>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GroupEntry {
+    Requirement(String),
+    Include {
+        #[serde(rename = "include-group")]
+        include_group: String,
+    },
 }
 
 #[serde_as]
 #[derive(Deserialize)]
-#[serde(default)]
+#[serde(default, rename_all = "kebab-case")]
 struct BuildSystem {
     #[serde(default = "BuildSystem::default_requires")]
     requires: Vec<String>,
+    #[serde_as(as = "DefaultOnError")]
+    build_backend: Option<String>,
 }
 
 #[serde_as]
@@ -46,12 +89,22 @@ struct Project {
     license: Option<String>,
     dependencies: Option<Vec<String>>,
     optional_dependencies: Option<BTreeMap<String, BTreeSet<String>>>,
+    scripts: BTreeMap<String, IgnoredAny>,
+    gui_scripts: BTreeMap<String, IgnoredAny>,
 }
 
+#[serde_as]
 #[derive(Default, Deserialize)]
 #[serde(default)]
 struct Tool {
     poetry: Poetry,
+    pytest: Option<IgnoredAny>,
+    #[serde_as(as = "DefaultOnError")]
+    pdm: Pdm,
+    #[serde_as(as = "DefaultOnError")]
+    hatch: Hatch,
+    #[serde_as(as = "DefaultOnError")]
+    uv: Uv,
 }
 
 #[serde_as]
@@ -64,6 +117,9 @@ struct Poetry {
     #[serde_as(as = "Option<BTreeMap<_, DefaultOnError>>")]
     dependencies: Option<BTreeMap<String, PoetryDependency>>,
     extras: BTreeMap<String, BTreeSet<String>>,
+    #[serde_as(as = "BTreeMap<_, DefaultOnError>")]
+    group: BTreeMap<String, PoetryGroup>,
+    scripts: BTreeMap<String, IgnoredAny>,
 }
 
 #[serde_as]
@@ -72,6 +128,90 @@ struct PoetryDependency {
     optional: bool,
 }
 
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PoetryGroup {
+    dependencies: BTreeMap<String, IgnoredAny>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct Pdm {
+    // superseded by dependency-groups
+    dev_dependencies: BTreeMap<String, Vec<String>>,
+}
+
+#[serde_as]
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Hatch {
+    #[serde_as(as = "BTreeMap<_, DefaultOnError>")]
+    envs: BTreeMap<String, HatchEnv>,
+    metadata: HatchMetadata,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct HatchEnv {
+    dependencies: Vec<String>,
+    extra_dependencies: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct HatchMetadata {
+    hooks: HatchHooks,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct HatchHooks {
+    // https://github.com/repo-helper/hatch-requirements-txt
+    #[serde(rename = "requirements_txt")]
+    requirements_txt: Option<HatchRequirementsTxt>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct HatchRequirementsTxt {
+    files: Vec<PathBuf>,
+    optional_dependencies: BTreeMap<String, Vec<PathBuf>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct Uv {
+    // superseded by dependency-groups
+    dev_dependencies: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct UvLock {
+    package: Vec<UvPackage>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct UvPackage {
+    source: UvSource,
+    dependencies: Vec<UvDependency>,
+    optional_dependencies: BTreeMap<String, Vec<UvDependency>>,
+    dev_dependencies: BTreeMap<String, Vec<UvDependency>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct UvSource {
+    editable: Option<String>,
+    r#virtual: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UvDependency {
+    name: String,
+}
+
 impl Pyproject {
     pub fn from_path(path: PathBuf) -> Pyproject {
         let Ok(content) = &fs::read_to_string(path) else {
@@ -89,6 +229,18 @@ impl Pyproject {
             .or_else(|| self.tool.poetry.name.take())
     }
 
+    // names of the executables created from entry points
+    pub fn get_scripts(&self) -> Vec<String> {
+        self.project
+            .scripts
+            .keys()
+            .chain(self.project.gui_scripts.keys())
+            .chain(self.tool.poetry.scripts.keys())
+            .unique()
+            .cloned()
+            .collect()
+    }
+
     pub fn load_license(&self, licenses: &mut BTreeMap<&'static str, f32>) {
         if let Some(license) = self
             .project
@@ -102,33 +254,150 @@ impl Pyproject {
         }
     }
 
-    pub fn load_build_dependencies(&self, inputs: &mut AllInputs, application: bool) {
+    pub fn load_build_dependencies(
+        &self,
+        inputs: &mut AllInputs,
+        mappings: &Mappings,
+        application: bool,
+    ) {
+        for (name, key) in self.build_requirements() {
+            let reason = format!("build-system {key} {name}");
+            if let Some(mapping) = mappings.python.get(&name) {
+                mapping.apply(inputs, &reason);
+                continue;
+            }
+
+            input_macros!(inputs, reason);
+            match name.as_str() {
+                "maturin" => native_build!("rustPlatform.maturinBuildHook"),
+                _ => native_build!(if application {
+                    format!("python3Packages.{name}")
+                } else {
+                    name
+                }),
+            }
+        }
+    }
+
+    // detects C, C++, and Cython extensions and the tools and libraries they need
+    pub fn load_native_dependencies(
+        &self,
+        src_dir: &Path,
+        inputs: &mut AllInputs,
+        application: bool,
+    ) {
+        for (name, _) in self.build_requirements() {
+            input_macros!(inputs, format_args!("build-system requires {name}"));
+            match name.as_str() {
+                "meson-python" => native_build!("meson", "ninja", "pkg-config"),
+                "scikit-build" | "scikit-build-core" => native_build!("cmake", "ninja"),
+                _ => {}
+            }
+        }
+
+        if find_file(src_dir, 4, &|name| {
+            name.ends_with(".pyx") || name.ends_with(".pxd")
+        }) {
+            input_macros!(inputs, "Cython sources");
+            native_build!(if application {
+                "python3Packages.cython"
+            } else {
+                "cython"
+            });
+        }
+
+        if let Ok(setup) = fs::read_to_string(src_dir.join("setup.py"))
+            && (setup.contains("ext_modules") || setup.contains("Extension("))
+        {
+            for lib in captures(r"\blibraries\s*=\s*\[([^\]]*)\]", &setup)
+                .iter()
+                .flat_map(|libs| captures(r#"["']([^"']+)["']"#, libs))
+            {
+                if let Some(input) = library_input(&lib) {
+                    input_macros!(inputs, format_args!("setup.py links to {lib}"));
+                    build!(input);
+                }
+            }
+
+            for module in captures(r#"\bpkgconfig\.\w+\(\s*["']([^"']+)["']"#, &setup) {
+                input_macros!(inputs, format_args!("setup.py requires {module}"));
+                native_build!("pkg-config");
+                build!(pkg_config_input(&module));
+            }
+        }
+
+        if let Ok(meson) = fs::read_to_string(src_dir.join("meson.build")) {
+            for module in captures(r#"(?:^|[^.\w])dependency\(\s*'([^']+)'"#, &meson) {
+                if matches!(
+                    module.as_str(),
+                    "dl" | "numpy" | "openmp" | "pybind11" | "python" | "python3" | "threads",
+                ) {
+                    continue;
+                }
+                input_macros!(inputs, format_args!("meson.build requires {module}"));
+                native_build!("pkg-config");
+                build!(pkg_config_input(&module));
+            }
+        }
+
+        if let Ok(cmake) = fs::read_to_string(src_dir.join("CMakeLists.txt")) {
+            for args in captures(r"(?i)\bpkg_check_modules\(\s*\w+([^)]*)\)", &cmake) {
+                for module in args.split_whitespace() {
+                    if module.starts_with(|c: char| c.is_ascii_uppercase()) {
+                        // keywords such as REQUIRED and IMPORTED_TARGET
+                        continue;
+                    }
+                    // strip version constraints such as glib-2.0>=2.56
+                    let module = module.split(['<', '>', '=']).next().unwrap_or(module);
+                    input_macros!(inputs, format_args!("CMakeLists.txt requires {module}"));
+                    native_build!("pkg-config");
+                    build!(pkg_config_input(module));
+                }
+            }
+        }
+    }
+
+    // normalized names of the build requirements along with the key they come from
+    fn build_requirements(&self) -> Vec<(String, &'static str)> {
         let parser = parser();
-        for dep in self
+        let mut requires: Vec<_> = self
             .build_system
             .requires
             .iter()
             .filter_map(|dep| parser.parse(dep.as_str()).into_output())
+            .map(|dep| (dep.name.to_lowercase().to_kebab_case(), "requires"))
+            .collect();
+
+        // the backend is sometimes provided by a requirement with a different name,
+        // e.g. hatchling.build by hatchling[version] or setuptools.build_meta by setuptools-scm
+        if let Some(backend) = self
+            .build_system
+            .build_backend
+            .as_deref()
+            .and_then(backend_requirement)
+            && !requires.iter().any(|(name, _)| name == backend)
         {
-            match dep.name {
-                "maturin" => {
-                    inputs
-                        .native_build_inputs
-                        .always
-                        .insert("rustPlatform.maturinBuildHook".into());
-                }
-                name => {
-                    inputs.native_build_inputs.always.insert(if application {
-                        format!("python3Packages.{}", AsKebabCase(name.to_lowercase()))
-                    } else {
-                        name.to_lowercase().to_kebab_case()
-                    });
-                }
-            }
+            requires.push((backend.into(), "build-backend"));
+        }
+
+        requires
+    }
+
+    pub fn get_dependencies(&mut self, src_dir: &Path) -> Option<PythonDependencies> {
+        let mut deps = self.get_main_dependencies(src_dir);
+        let groups = self.get_dependency_groups();
+        if groups.is_empty() {
+            return deps;
         }
+
+        let all_groups = &mut deps.get_or_insert_default().groups;
+        for (group, names) in groups {
+            all_groups.entry(group).or_default().extend(names);
+        }
+        deps
     }
 
-    pub fn get_dependencies(&mut self) -> Option<PythonDependencies> {
+    fn get_main_dependencies(&mut self, src_dir: &Path) -> Option<PythonDependencies> {
         if let Some(mut deps) = self.tool.poetry.dependencies.take() {
             deps.remove("python");
             return Some(PythonDependencies {
@@ -139,9 +408,31 @@ impl Pyproject {
                     })
                     .collect(),
                 optional: mem::take(&mut self.tool.poetry.extras),
+                ..Default::default()
             });
         }
 
+        // dynamic dependencies from hatch-requirements-txt
+        if self.project.dependencies.is_none()
+            && self.project.optional_dependencies.is_none()
+            && let Some(hook) = self.tool.hatch.metadata.hooks.requirements_txt.take()
+        {
+            let read = |files: Vec<PathBuf>| {
+                files
+                    .into_iter()
+                    .filter_map(|file| fs::read_to_string(src_dir.join(file)).ok())
+                    .flat_map(|content| content.lines().map(Into::into).collect::<Vec<_>>())
+                    .collect::<Vec<String>>()
+            };
+            self.project.dependencies = Some(read(hook.files));
+            self.project.optional_dependencies = Some(
+                hook.optional_dependencies
+                    .into_iter()
+                    .map(|(extra, files)| (extra, read(files).into_iter().collect()))
+                    .collect(),
+            );
+        }
+
         match (
             self.project.dependencies.take(),
             self.project.optional_dependencies.take(),
@@ -151,20 +442,178 @@ impl Pyproject {
                 let mut all_deps = always.map_or_else(Default::default, get_python_dependencies);
 
                 for (extra, deps) in optional {
-                    let entry = all_deps.optional.entry(extra).or_default();
                     let parser = parser();
                     for dep in &deps {
-                        if let Some(Dependency { name, .. }) = parser.parse(dep).into_output() {
-                            entry.insert(name.to_lowercase().to_kebab_case());
+                        if let Some(dep) = parser.parse(dep).into_output()
+                            && let Some(name) = all_deps.load_dependency(&dep)
+                        {
+                            all_deps
+                                .optional
+                                .entry(extra.clone())
+                                .or_default()
+                                .insert(name);
                         }
                     }
                 }
 
                 Some(all_deps)
             }
-            (None, None) => None,
+            // dependencies can be dynamic, uv.lock records them either way
+            (None, None) => parse_uv_lock(src_dir),
         }
     }
+
+    fn get_dependency_groups(&mut self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut groups = BTreeMap::new();
+
+        for name in self.dependency_groups.keys() {
+            let mut deps = BTreeSet::new();
+            self.resolve_group(name, &mut deps, &mut BTreeSet::new());
+            groups.insert(name.clone(), deps);
+        }
+
+        let mut insert = |group: String, deps: Vec<String>| {
+            groups
+                .entry(group)
+                .or_insert_with(BTreeSet::new)
+                .extend(dependency_names(&deps));
+        };
+        for (group, deps) in mem::take(&mut self.tool.pdm.dev_dependencies) {
+            insert(group, deps);
+        }
+        insert("dev".into(), mem::take(&mut self.tool.uv.dev_dependencies));
+        for (
+            env,
+            HatchEnv {
+                mut dependencies,
+                extra_dependencies,
+            },
+        ) in mem::take(&mut self.tool.hatch.envs)
+        {
+            dependencies.extend(extra_dependencies);
+            insert(env, dependencies);
+        }
+        for (group, PoetryGroup { dependencies }) in mem::take(&mut self.tool.poetry.group) {
+            insert(group, dependencies.into_keys().collect());
+        }
+
+        groups.retain(|_, deps| !deps.is_empty());
+        groups
+    }
+
+    // https://peps.python.org/pep-0735/#dependency-group-include
+    fn resolve_group<'a>(
+        &'a self,
+        name: &'a str,
+        deps: &mut BTreeSet<String>,
+        visited: &mut BTreeSet<&'a str>,
+    ) {
+        if !visited.insert(name) {
+            return;
+        }
+        let Some(entries) = self.dependency_groups.get(name) else {
+            return;
+        };
+
+        for entry in entries {
+            match entry {
+                GroupEntry::Requirement(dep) => {
+                    deps.extend(dependency_names([dep]));
+                }
+                GroupEntry::Include { include_group } => {
+                    self.resolve_group(include_group, deps, visited);
+                }
+            }
+        }
+    }
+}
+
+// maps PEP 517 build backends to the requirements providing them
+fn backend_requirement(backend: &str) -> Option<&'static str> {
+    Some(match backend.split(':').next()? {
+        // keep-sorted start
+        "flit_core.buildapi" => "flit-core",
+        "hatchling.build" => "hatchling",
+        "maturin" => "maturin",
+        "mesonpy" => "meson-python",
+        "pdm.backend" => "pdm-backend",
+        "poetry.core.masonry.api" => "poetry-core",
+        "scikit_build_core.build" => "scikit-build-core",
+        "setuptools.build_meta" => "setuptools",
+        "uv_build" => "uv-build",
+        // keep-sorted end
+        _ => return None,
+    })
+}
+
+fn captures(re: &str, haystack: &str) -> Vec<String> {
+    let Some(re) = Regex::new(re).ok_inspect(|e| warn!("{e}")) else {
+        return Vec::new();
+    };
+    re.captures_iter(haystack)
+        .map(|caps| caps[1].into())
+        .collect()
+}
+
+// searches the source tree for a file, skipping hidden directories
+fn find_file(dir: &Path, depth: usize, pred: &impl Fn(&str) -> bool) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+
+    entries.flatten().any(|entry| {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            return false;
+        };
+        match entry.file_type() {
+            Ok(ty) if ty.is_dir() => {
+                depth > 0 && !name.starts_with('.') && find_file(&entry.path(), depth - 1, pred)
+            }
+            Ok(ty) if ty.is_file() => pred(name),
+            _ => false,
+        }
+    })
+}
+
+fn parse_uv_lock(src_dir: &Path) -> Option<PythonDependencies> {
+    let lock: UvLock = toml::from_str(&fs::read_to_string(src_dir.join("uv.lock")).ok()?)
+        .ok_inspect(|e| warn!("{e}"))?;
+    let root = lock.package.into_iter().find(|pkg| {
+        pkg.source.editable.as_deref() == Some(".") || pkg.source.r#virtual.as_deref() == Some(".")
+    })?;
+
+    let names = |deps: Vec<UvDependency>| {
+        deps.into_iter()
+            .map(|dep| dep.name.to_lowercase().to_kebab_case())
+            .collect()
+    };
+    Some(PythonDependencies {
+        always: names(root.dependencies),
+        optional: root
+            .optional_dependencies
+            .into_iter()
+            .map(|(extra, deps)| (extra, names(deps)))
+            .collect(),
+        groups: root
+            .dev_dependencies
+            .into_iter()
+            .map(|(group, deps)| (group, names(deps)))
+            .collect(),
+        ..Default::default()
+    })
+}
+
+// normalized names of requirements, without recording their constraints
+fn dependency_names(xs: impl IntoIterator<Item = impl AsRef<str>>) -> BTreeSet<String> {
+    xs.into_iter()
+        .filter_map(|dep| {
+            parser()
+                .parse(dep.as_ref())
+                .into_output()
+                .map(|dep| dep.name.to_lowercase().to_kebab_case())
+        })
+        .collect()
 }
 
 pub fn parse_requirements_txt(src: &Path) -> Option<PythonDependencies> {
@@ -185,12 +634,14 @@ pub fn get_python_dependencies(
         let Some(dep) = parser().parse(dep.as_ref()).into_output() else {
             continue;
         };
+        let Some(name) = deps.load_dependency(&dep) else {
+            continue;
+        };
         let mut extras = Vec::new();
         if let Some(marker) = dep.marker {
             load_extras(&mut extras, marker);
         }
 
-        let name = dep.name.to_lowercase().to_kebab_case();
         if extras.is_empty() {
             deps.always.insert(name);
         } else {
@@ -202,6 +653,203 @@ pub fn get_python_dependencies(
     deps
 }
 
+impl PythonDependencies {
+    // returns the name of the dependency unless it should be removed
+    fn load_dependency(&mut self, dep: &Dependency) -> Option<String> {
+        // backports of modules in the standard library
+        if matches!(
+            dep.name.to_lowercase().as_str(),
+            "argparse" | "asyncio" | "dataclasses" | "enum34" | "futures" | "pathlib" | "typing",
+        ) {
+            self.remove.insert(dep.name.into());
+            return None;
+        }
+
+        let name = dep.name.to_lowercase().to_kebab_case();
+        if let Some(Spec::Version(specs)) = &dep.spec
+            && !specs.is_empty()
+        {
+            self.constraints
+                .entry(name.clone())
+                .or_insert_with(|| Constraint {
+                    name: dep.name.into(),
+                    specs: Vec::new(),
+                })
+                .specs
+                .extend(
+                    specs
+                        .iter()
+                        .map(|spec| (spec.comparator, spec.version.into())),
+                );
+        }
+
+        Some(name)
+    }
+
+    // moves the dependencies in the test extras and groups to the check inputs if pytest is used
+    pub fn load_check_dependencies(&mut self, src_dir: &Path, pyproject: &Pyproject) {
+        const TEST_EXTRAS: [&str; 4] = ["test", "tests", "testing", "hatch-test"];
+
+        let mut deps: BTreeSet<_> = TEST_EXTRAS
+            .iter()
+            .flat_map(|extra| [self.optional.get(*extra), self.groups.get(*extra)])
+            .flatten()
+            .flatten()
+            .cloned()
+            .collect();
+        for dev in [self.optional.get("dev"), self.groups.get("dev")]
+            .into_iter()
+            .flatten()
+        {
+            deps.extend(dev.iter().filter(|dep| dep.starts_with("pytest")).cloned());
+        }
+
+        let contains = |file, pattern| {
+            fs::read_to_string(src_dir.join(file)).is_ok_and(|content| content.contains(pattern))
+        };
+        if !(deps.contains("pytest")
+            || pyproject.tool.pytest.is_some()
+            || src_dir.join("pytest.ini").is_file()
+            || src_dir.join("conftest.py").is_file()
+            || contains("setup.cfg", "[tool:pytest]")
+            || contains("tox.ini", "pytest"))
+        {
+            return;
+        }
+
+        for extra in TEST_EXTRAS {
+            self.optional.remove(extra);
+        }
+
+        self.check.insert("pytestCheckHook".into());
+        for dep in deps {
+            match dep.as_str() {
+                "pytest" => {}
+                // coverage reports are not useful when packaging
+                "pytest-cov" => {
+                    self.check.insert("pytest-cov-stub".into());
+                }
+                _ => {
+                    self.check.insert(dep);
+                }
+            }
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.always
+            .iter()
+            .chain(self.optional.values().flatten())
+            .chain(&self.check)
+    }
+
+    pub fn rename(&mut self, old: &str, new: &str) {
+        for deps in [&mut self.always, &mut self.check]
+            .into_iter()
+            .chain(self.optional.values_mut())
+        {
+            if deps.remove(old) {
+                deps.insert(new.into());
+            }
+        }
+        if let Some(constraint) = self.constraints.remove(old) {
+            self.constraints.insert(new.into(), constraint);
+        }
+    }
+
+    // relaxes the requirements that the versions in nixpkgs don't satisfy
+    pub fn relax_constraints<'a>(&mut self, version: impl Fn(&str) -> Option<&'a str>) {
+        for (name, constraint) in &self.constraints {
+            if let Some(version) = version(name)
+                && !constraint.allows(version)
+            {
+                self.relax.insert(constraint.name.clone());
+            }
+        }
+    }
+}
+
+impl Constraint {
+    fn allows(&self, version: &str) -> bool {
+        let Some(version) = parse_version(version) else {
+            return true;
+        };
+
+        self.specs.iter().all(|(comparator, spec)| {
+            if let Some(prefix) = spec.strip_suffix(".*") {
+                let matches =
+                    parse_version(prefix).is_none_or(|(prefix, _)| version.0.starts_with(&prefix));
+                return match comparator {
+                    Comparator::Eq => matches,
+                    Comparator::Ne => !matches,
+                    _ => true,
+                };
+            }
+
+            let Some(spec) = parse_version(spec) else {
+                return true;
+            };
+            let ord = cmp_versions(&version, &spec);
+            match comparator {
+                Comparator::Lt => ord.is_lt(),
+                Comparator::Le => ord.is_le(),
+                Comparator::Ne => ord.is_ne(),
+                Comparator::Eq | Comparator::Ae => ord.is_eq(),
+                Comparator::Ge => ord.is_ge(),
+                Comparator::Gt => ord.is_gt(),
+                Comparator::Cp => {
+                    ord.is_ge()
+                        && version
+                            .0
+                            .starts_with(&spec.0[.. spec.0.len().saturating_sub(1)])
+                }
+            }
+        })
+    }
+}
+
+// the release segment of a version and whether it is a pre-release
+// epochs, post-releases, and local versions are ignored
+fn parse_version(version: &str) -> Option<(Vec<u64>, bool)> {
+    let version = version
+        .split_once('!')
+        .map_or(version, |(_, version)| version);
+    let version = version
+        .split_once('+')
+        .map_or(version, |(version, _)| version);
+
+    let mut release = Vec::new();
+    let mut pre = false;
+    for part in version.trim_start_matches('v').split('.') {
+        let end = part
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(part.len());
+        if end != 0 {
+            release.push(part[.. end].parse().ok()?);
+        }
+        if end != part.len() {
+            pre = !part[end ..]
+                .trim_start_matches(['-', '_'])
+                .starts_with("post");
+            break;
+        }
+    }
+
+    (!release.is_empty()).then_some((release, pre))
+}
+
+fn cmp_versions((x, x_pre): &(Vec<u64>, bool), (y, y_pre): &(Vec<u64>, bool)) -> Ordering {
+    let len = x.len().max(y.len());
+    let pad = |xs: &Vec<u64>| {
+        xs.iter()
+            .copied()
+            .chain(repeat(0))
+            .take(len)
+            .collect::<Vec<_>>()
+    };
+    pad(x).cmp(&pad(y)).then(y_pre.cmp(x_pre))
+}
+
 fn load_extras(extras: &mut Vec<String>, marker: Marker) {
     match marker {
         Marker::And(x, y) | Marker::Or(x, y) => {
@@ -238,6 +886,255 @@ impl Default for BuildSystem {
     fn default() -> Self {
         Self {
             requires: Self::default_requires(),
+            build_backend: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use indoc::indoc;
+    use tempfile::tempdir;
+
+    use super::{Pyproject, get_python_dependencies};
+    use crate::{cfg::Mappings, inputs::AllInputs};
+
+    #[test]
+    fn relax() {
+        let mut deps = get_python_dependencies([
+            "Click>=8,<8.1",
+            "rich~=13.3",
+            "requests==2.*",
+            "attrs>=23.1.0",
+            "packaging!=24.0",
+            "dataclasses; python_version < '3.7'",
+            "unknown<1",
+        ]);
+        assert_eq!(Vec::from_iter(&deps.remove), ["dataclasses"]);
+
+        deps.relax_constraints(|name| match name {
+            "click" => Some("8.1.7"),
+            "rich" => Some("14.0.0"),
+            "requests" => Some("2.32.3"),
+            "attrs" => Some("23.1"),
+            "packaging" => Some("24.0"),
+            _ => None,
+        });
+        assert_eq!(Vec::from_iter(&deps.relax), ["Click", "packaging", "rich"],);
+    }
+
+    #[test]
+    fn check() {
+        let dir = tempdir().unwrap();
+        let mut deps = get_python_dependencies([
+            "requests",
+            "pytest; extra == 'test'",
+            "pytest-cov; extra == 'test'",
+            "hypothesis; extra == 'test'",
+            "black; extra == 'dev'",
+            "pytest-xdist; extra == 'dev'",
+        ]);
+        deps.load_check_dependencies(dir.path(), &Pyproject::default());
+
+        assert_eq!(
+            Vec::from_iter(&deps.check),
+            [
+                "hypothesis",
+                "pytest-cov-stub",
+                "pytest-xdist",
+                "pytestCheckHook",
+            ],
+        );
+        assert_eq!(Vec::from_iter(deps.optional.keys()), ["dev"]);
+
+        let mut deps = get_python_dependencies(["hypothesis; extra == 'tests'"]);
+        deps.load_check_dependencies(dir.path(), &Pyproject::default());
+        assert!(deps.check.is_empty());
+        assert_eq!(Vec::from_iter(deps.optional.keys()), ["tests"]);
+    }
+
+    #[test]
+    fn groups() {
+        let dir = tempdir().unwrap();
+        let mut pyproject: Pyproject = toml::from_str(indoc! {r#"
+            [build-system]
+            requires = ["hatchling", "hatch-vcs"]
+            build-backend = "hatchling.build"
+
+            [project]
+            dependencies = ["httpx>=0.27"]
+
+            [dependency-groups]
+            test = ["pytest>=8", "pytest-cov", "respx"]
+            dev = [{ include-group = "test" }, "ruff"]
+
+            [tool.pdm.dev-dependencies]
+            lint = ["mypy"]
+        "#})
+        .unwrap();
+
+        let mut deps = pyproject.get_dependencies(dir.path()).unwrap();
+        assert_eq!(Vec::from_iter(&deps.always), ["httpx"]);
+        assert_eq!(Vec::from_iter(deps.groups.keys()), ["dev", "lint", "test"]);
+        assert_eq!(
+            Vec::from_iter(&deps.groups["dev"]),
+            ["pytest", "pytest-cov", "respx", "ruff"],
+        );
+
+        deps.load_check_dependencies(dir.path(), &pyproject);
+        assert_eq!(
+            Vec::from_iter(&deps.check),
+            ["pytest-cov-stub", "pytestCheckHook", "respx"],
+        );
+
+        let mut inputs = AllInputs::default();
+        pyproject.load_build_dependencies(&mut inputs, &Mappings::default(), false);
+        assert_eq!(
+            Vec::from_iter(&inputs.native_build_inputs.always),
+            ["hatch-vcs", "hatchling"],
+        );
+    }
+
+    #[test]
+    fn uv() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("uv.lock"),
+            indoc! {r#"
+                version = 1
+
+                [[package]]
+                name = "click"
+                version = "8.1.7"
+                source = { registry = "https://pypi.org/simple" }
+
+                [[package]]
+                name = "foo"
+                version = "0.1.0"
+                source = { editable = "." }
+                dependencies = [
+                    { name = "click" },
+                    { name = "typing-extensions", marker = "python_full_version < '3.11'" },
+                ]
+
+                [package.optional-dependencies]
+                yaml = [{ name = "pyyaml" }]
+
+                [package.dev-dependencies]
+                dev = [{ name = "pytest" }]
+            "#},
+        )
+        .unwrap();
+
+        let mut pyproject: Pyproject = toml::from_str(indoc! {r#"
+            [build-system]
+            requires = ["uv_build>=0.8,<0.9"]
+            build-backend = "uv_build"
+
+            [project]
+            name = "foo"
+            dynamic = ["dependencies"]
+        "#})
+        .unwrap();
+
+        let deps = pyproject.get_dependencies(dir.path()).unwrap();
+        assert_eq!(Vec::from_iter(&deps.always), ["click", "typing-extensions"]);
+        assert_eq!(Vec::from_iter(&deps.optional["yaml"]), ["pyyaml"]);
+        assert_eq!(Vec::from_iter(&deps.groups["dev"]), ["pytest"]);
+
+        let mut inputs = AllInputs::default();
+        pyproject.load_build_dependencies(&mut inputs, &Mappings::default(), true);
+        assert_eq!(
+            Vec::from_iter(&inputs.native_build_inputs.always),
+            ["python3Packages.uv-build"],
+        );
+    }
+
+    #[test]
+    fn backend() {
+        let pyproject: Pyproject = toml::from_str(indoc! {r#"
+            [build-system]
+            requires = ["setuptools-scm"]
+            build-backend = "setuptools.build_meta"
+        "#})
+        .unwrap();
+
+        let mut inputs = AllInputs::default();
+        pyproject.load_build_dependencies(&mut inputs, &Mappings::default(), false);
+        assert_eq!(
+            Vec::from_iter(&inputs.native_build_inputs.always),
+            ["setuptools", "setuptools-scm"],
+        );
+        assert_eq!(
+            Vec::from_iter(&inputs.reasons["setuptools"]),
+            ["build-system build-backend setuptools"],
+        );
+    }
+
+    #[test]
+    fn native() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src").join("_speedups.pyx"), "").unwrap();
+        fs::write(
+            dir.path().join("setup.py"),
+            indoc! {r#"
+                from setuptools import Extension, setup
+                import pkgconfig
+
+                setup(
+                    ext_modules=[
+                        Extension(
+                            "foo._speedups",
+                            ["src/_speedups.pyx"],
+                            libraries=["z", "m", 'crypto'],
+                            **pkgconfig.parse("libxml-2.0"),
+                        ),
+                    ],
+                )
+            "#},
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("CMakeLists.txt"),
+            "pkg_check_modules(GLIB REQUIRED IMPORTED_TARGET glib-2.0>=2.56)",
+        )
+        .unwrap();
+
+        let pyproject: Pyproject = toml::from_str(indoc! {r#"
+            [build-system]
+            requires = ["scikit-build-core"]
+        "#})
+        .unwrap();
+
+        let mut inputs = AllInputs::default();
+        pyproject.load_native_dependencies(dir.path(), &mut inputs, true);
+        assert_eq!(
+            Vec::from_iter(&inputs.native_build_inputs.always),
+            ["cmake", "ninja", "pkg-config", "python3Packages.cython"],
+        );
+        assert_eq!(
+            Vec::from_iter(&inputs.build_inputs.always),
+            ["glib", "libxml2", "openssl", "zlib"],
+        );
+    }
+
+    #[test]
+    fn scripts() {
+        let pyproject: Pyproject = toml::from_str(indoc! {r#"
+            [project.scripts]
+            foo = "foo.cli:main"
+            foo-admin = "foo.admin:main"
+
+            [project.gui-scripts]
+            foo-gui = "foo.gui:main"
+
+            [tool.poetry.scripts]
+            foo = "foo.cli:main"
+        "#})
+        .unwrap();
+        assert_eq!(pyproject.get_scripts(), ["foo", "foo-admin", "foo-gui"]);
+    }
+}
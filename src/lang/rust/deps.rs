@@ -3,8 +3,13 @@ use semver::Version;
 
 use crate::{inputs::AllInputs, macros::input_macros};
 
-pub(super) fn load_rust_dependency(inputs: &mut AllInputs, resolve: &Resolve, pkg: PackageId) {
-    input_macros!(inputs);
+// returns false if the crate is unknown
+pub(super) fn load_rust_dependency(
+    inputs: &mut AllInputs,
+    resolve: &Resolve,
+    pkg: PackageId,
+) -> bool {
+    input_macros!(inputs, pkg.name());
 
     match &*pkg.name() {
         // keep-sorted start group_start_regex=^\s*"
@@ -136,7 +141,7 @@ pub(super) fn load_rust_dependency(inputs: &mut AllInputs, resolve: &Resolve, pk
             let mut stock = false;
             for feat in resolve.features(pkg) {
                 match &**feat {
-                    "static" => return,
+                    "static" => return true,
                     "stock-zlib" => stock = true,
                     "zlib-ng" => ng = true,
                     _ => {}
@@ -244,6 +249,8 @@ pub(super) fn load_rust_dependency(inputs: &mut AllInputs, resolve: &Resolve, pk
             }
         }
         // keep-sorted end
-        _ => {}
+        _ => return false,
     }
+
+    true
 }
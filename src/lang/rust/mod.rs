@@ -1,4 +1,5 @@
 mod deps;
+mod sys;
 #[cfg(test)]
 mod tests;
 
@@ -7,14 +8,14 @@ use std::{
     fmt::{Display, Write},
     fs::File,
     io::{self, Write as _},
-    path::Path,
+    path::{Path, PathBuf},
     process::Command,
 };
 
 use anyhow::{Context, Result, anyhow};
 use cargo::{
     core::{
-        Resolve, Workspace,
+        Package, Resolve, Workspace,
         registry::PackageRegistry,
         resolver::{CliFeatures, HasDevUnits},
     },
@@ -24,35 +25,75 @@ use cargo::{
 };
 use cargo_util_terminal::Shell;
 use indoc::writedoc;
+use itertools::Itertools;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use reqwest::Client;
 use rustc_hash::FxHashMap;
+use tokio::task::JoinSet;
 use tracing::{error, warn};
 
 use crate::{
+    cfg::Mappings,
     cmd::NURL,
     frontend::FrontendDispatch,
     inputs::AllInputs,
-    lang::rust::deps::load_rust_dependency,
+    lang::{
+        pkg_config::{PkgConfigIndex, pkg_config_input},
+        rust::{
+            deps::load_rust_dependency,
+            sys::{SysCrate, sys_crate},
+        },
+    },
+    macros::input_macros,
     utils::{CommandExt, FAKE_HASH, ResultExt, fod_hash},
 };
 
+// features to enable when building the package
+#[derive(Clone, Default)]
+pub struct CargoFeatures {
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+}
+
+// the members and features to load the dependencies of,
+// all the features when they are not specified
+#[derive(Clone, Copy)]
+pub struct CargoSelection<'a> {
+    pub members: &'a [String],
+    pub features: Option<&'a CargoFeatures>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn cargo_deps_hash(
     inputs: &mut AllInputs,
+    mappings: &Mappings,
+    selection: CargoSelection<'_>,
     pname: impl Display,
     version: impl Display,
     src: impl Display,
     src_dir: &Path,
     has_cargo_lock: bool,
     nixpkgs: &str,
+    // the hash from a previous attempt of --build
+    cached: Option<String>,
+    crates_io: Option<&Client>,
 ) -> String {
     if has_cargo_lock {
         let (hash, _) = tokio::join!(
-            fod_hash(format!(
-                r#"(import({nixpkgs}){{}}).rustPlatform.fetchCargoVendor{{pname="{pname}";version="{version}";src={src};hash="{FAKE_HASH}";}}"#,
-            )),
+            async {
+                match cached {
+                    Some(hash) => Some(hash),
+                    None => fod_hash(format!(
+                        r#"(import({nixpkgs}){{}}).rustPlatform.fetchCargoVendor{{pname="{pname}";version="{version}";src={src};hash="{FAKE_HASH}";}}"#,
+                    ))
+                    .await,
+                }
+            },
             async {
                 if let Some(lock) = resolve_workspace(src_dir) {
-                    load_rust_dependencies(inputs, &lock);
+                    let sys_crates =
+                        load_selected_dependencies(inputs, mappings, src_dir, &lock, selection);
+                    load_pkg_config_probes(inputs, nixpkgs, sys_crates, crates_io).await;
                 }
             }
         );
@@ -62,12 +103,17 @@ pub async fn cargo_deps_hash(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn load_cargo_lock(
     frontend: &mut FrontendDispatch,
     out_dir: &Path,
     inputs: &mut AllInputs,
+    mappings: &Mappings,
+    selection: CargoSelection<'_>,
     src_dir: &Path,
+    nixpkgs: &str,
     opt_overwrite: Option<bool>,
+    crates_io: Option<&Client>,
 ) -> Result<Option<Resolve>> {
     let target = &out_dir.join("Cargo.lock");
     let resolve = match File::open(target) {
@@ -121,8 +167,10 @@ pub async fn load_cargo_lock(
         }
     };
 
+    // the lock file covers all features, unlike the dependencies loaded from it
     if let Some(lock) = &resolve {
-        load_rust_dependencies(inputs, lock);
+        let sys_crates = load_selected_dependencies(inputs, mappings, src_dir, lock, selection);
+        load_pkg_config_probes(inputs, nixpkgs, sys_crates, crates_io).await;
     }
 
     Ok(resolve)
@@ -183,6 +231,87 @@ pub async fn write_cargo_lock(
     Ok(())
 }
 
+pub struct CargoMember {
+    pub name: String,
+    // relative to the root of the workspace
+    pub path: PathBuf,
+}
+
+// names of the binary targets in the given members of the workspace,
+// or in the default members if none are given
+pub fn cargo_bins(src_dir: &Path, members: &[String]) -> Vec<String> {
+    let Some(cfg) = cargo_config(src_dir).ok_inspect(|e| warn!("{e}")) else {
+        return Vec::new();
+    };
+    let Some(ws) = cargo_workspace(src_dir, &cfg).ok_inspect(|e| warn!("{e}")) else {
+        return Vec::new();
+    };
+
+    let bins = |pkg: &Package| {
+        pkg.targets()
+            .iter()
+            .filter(|target| target.is_bin())
+            .map(|target| target.name().to_owned())
+            .collect::<Vec<_>>()
+    };
+    selected_members(&ws, members)
+        .flat_map(bins)
+        .unique()
+        .collect()
+}
+
+fn selected_members<'a>(
+    ws: &'a Workspace<'_>,
+    members: &'a [String],
+) -> Box<dyn Iterator<Item = &'a Package> + 'a> {
+    if members.is_empty() {
+        Box::new(ws.default_members())
+    } else {
+        Box::new(
+            ws.members()
+                .filter(|pkg| members.iter().any(|member| *member == *pkg.name())),
+        )
+    }
+}
+
+// features of the given members of the workspace, or of the default members if none are given
+pub fn cargo_features(src_dir: &Path, members: &[String]) -> Vec<String> {
+    let Some(cfg) = cargo_config(src_dir).ok_inspect(|e| warn!("{e}")) else {
+        return Vec::new();
+    };
+    let Some(ws) = cargo_workspace(src_dir, &cfg).ok_inspect(|e| warn!("{e}")) else {
+        return Vec::new();
+    };
+
+    selected_members(&ws, members)
+        .flat_map(|pkg| pkg.summary().features().keys())
+        .map(ToString::to_string)
+        .sorted_unstable()
+        .dedup()
+        .collect()
+}
+
+// members of the workspace that produce binaries
+pub fn cargo_bin_members(src_dir: &Path) -> Vec<CargoMember> {
+    let Some(cfg) = cargo_config(src_dir).ok_inspect(|e| warn!("{e}")) else {
+        return Vec::new();
+    };
+    let Some(ws) = cargo_workspace(src_dir, &cfg).ok_inspect(|e| warn!("{e}")) else {
+        return Vec::new();
+    };
+
+    ws.members()
+        .filter(|pkg| pkg.targets().iter().any(|target| target.is_bin()))
+        .map(|pkg| CargoMember {
+            name: pkg.name().to_string(),
+            path: pkg
+                .root()
+                .strip_prefix(ws.root())
+                .map_or_else(|_| pkg.root().into(), Into::into),
+        })
+        .collect()
+}
+
 fn resolve_workspace(src_dir: &Path) -> Option<Resolve> {
     let mut cfg = cargo_config(src_dir).ok_inspect(|e| error!("{e}"))?;
     cfg.configure(0, false, None, false, true, false, &None, &[], &[])
@@ -208,6 +337,47 @@ fn resolve_workspace(src_dir: &Path) -> Option<Resolve> {
     .ok_inspect(|e| error!("{e}"))
 }
 
+// resolves the dependencies enabled by the selected members and features,
+// keeping the versions from the lock file
+fn resolve_selected(
+    src_dir: &Path,
+    lock: &Resolve,
+    members: &[String],
+    features: Option<&CargoFeatures>,
+) -> Option<Resolve> {
+    let mut cfg = cargo_config(src_dir).ok_inspect(|e| error!("{e}"))?;
+    cfg.configure(0, false, None, false, true, false, &None, &[], &[])
+        .ok_inspect(|e| error!("{e}"))?;
+
+    let ws = cargo_workspace(src_dir, &cfg).ok_inspect(|e| error!("{e}"))?;
+    let specs: Vec<_> = selected_members(&ws, members)
+        .map(|pkg| pkg.package_id().to_spec())
+        .collect();
+    let features = match features {
+        Some(features) => {
+            CliFeatures::from_command_line(&features.features, false, !features.no_default_features)
+        }
+        None => CliFeatures::from_command_line(&[], true, true),
+    }
+    .ok_inspect(|e| error!("{e}"))?;
+
+    let mut registry =
+        PackageRegistry::new_with_source_config(&cfg, SourceConfigMap::new(&cfg).ok()?)
+            .ok_inspect(|e| error!("{e}"))?;
+
+    resolve_with_previous(
+        &mut registry,
+        &ws,
+        &features,
+        HasDevUnits::Yes,
+        Some(lock),
+        None,
+        &specs,
+        true,
+    )
+    .ok_inspect(|e| error!("{e}"))
+}
+
 fn cargo_config(src_dir: &Path) -> Result<GlobalContext> {
     Ok(GlobalContext::new(
         Shell::new(),
@@ -223,8 +393,78 @@ fn cargo_workspace<'a>(src_dir: &Path, cfg: &'a GlobalContext) -> Result<Workspa
     Ok(ws)
 }
 
-fn load_rust_dependencies(inputs: &mut AllInputs, resolve: &Resolve) {
+// returns the unknown crates linking to native libraries
+fn load_selected_dependencies(
+    inputs: &mut AllInputs,
+    mappings: &Mappings,
+    src_dir: &Path,
+    lock: &Resolve,
+    selection: CargoSelection<'_>,
+) -> Vec<SysCrate> {
+    if selection.members.is_empty() && selection.features.is_none() {
+        return load_rust_dependencies(inputs, mappings, src_dir, lock);
+    }
+
+    match resolve_selected(src_dir, lock, selection.members, selection.features) {
+        Some(resolve) => load_rust_dependencies(inputs, mappings, src_dir, &resolve),
+        None => {
+            warn!("failed to resolve the selection, loading all the dependencies");
+            load_rust_dependencies(inputs, mappings, src_dir, lock)
+        }
+    }
+}
+
+fn load_rust_dependencies(
+    inputs: &mut AllInputs,
+    mappings: &Mappings,
+    src_dir: &Path,
+    resolve: &Resolve,
+) -> Vec<SysCrate> {
+    let mut sys_crates = Vec::new();
     for pkg in resolve.iter() {
-        load_rust_dependency(inputs, resolve, pkg);
+        let known = load_rust_dependency(inputs, resolve, pkg);
+        if let Some(mapping) = mappings.rust.get(&*pkg.name()) {
+            mapping.apply(inputs, &pkg.name());
+        } else if !known && let Some(sys_crate) = sys_crate(src_dir, resolve, pkg) {
+            sys_crates.push(sys_crate);
+        }
+    }
+    sys_crates
+}
+
+async fn load_pkg_config_probes(
+    inputs: &mut AllInputs,
+    nixpkgs: &str,
+    sys_crates: Vec<SysCrate>,
+    crates_io: Option<&Client>,
+) {
+    let mut tasks = JoinSet::new();
+    for sys_crate in sys_crates {
+        let crates_io = crates_io.cloned();
+        tasks.spawn(async move {
+            let modules = sys_crate.pkg_config_probes(crates_io.as_ref()).await;
+            (sys_crate.name, modules)
+        });
+    }
+
+    let mut probes = Vec::new();
+    while let Some(task) = tasks.join_next().await {
+        let Some((name, modules)) = task.ok_inspect(|e| warn!("{e}")) else {
+            continue;
+        };
+        probes.extend(modules.into_iter().map(|module| (name.clone(), module)));
+    }
+    if probes.is_empty() {
+        return;
+    }
+
+    let index = PkgConfigIndex::load(nixpkgs).await;
+    for (name, module) in probes {
+        input_macros!(inputs, &name);
+        native_build!("pkg-config");
+        build!(match &index {
+            Some(index) => index.get(&module),
+            None => pkg_config_input(&module),
+        });
     }
 }
@@ -0,0 +1,348 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, read_dir, read_to_string},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use cargo::{
+    core::{PackageId, Resolve},
+    util::homedir,
+};
+use flate2::read::GzDecoder;
+use regex::Regex;
+use reqwest::{Client, Response};
+use serde::{Deserialize, de::IgnoredAny};
+use tar::Archive;
+use tracing::{info, warn};
+
+use crate::utils::ResultExt;
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Manifest {
+    package: Package,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Package {
+    build: Option<toml::Value>,
+    metadata: Metadata,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+struct Metadata {
+    // https://docs.rs/system-deps
+    system_deps: BTreeMap<String, SystemDep>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SystemDep {
+    Table {
+        name: Option<String>,
+        #[serde(default)]
+        optional: bool,
+        feature: Option<String>,
+    },
+    Version(IgnoredAny),
+}
+
+// a crate that links to a native library and is not known to `load_rust_dependency`
+pub(super) struct SysCrate {
+    pub name: String,
+    version: String,
+    features: Vec<String>,
+    source: CrateSource,
+}
+
+enum CrateSource {
+    Dir(PathBuf),
+    // a .crate archive in the cargo registry cache
+    Archive(PathBuf),
+    // only downloaded with --download-crates
+    CratesIo,
+}
+
+enum CrateFiles {
+    Dir(PathBuf),
+    // Cargo.toml and the rust sources of a .crate archive, relative to its root
+    Archive(BTreeMap<PathBuf, String>),
+}
+
+pub(super) fn sys_crate(src_dir: &Path, resolve: &Resolve, pkg: PackageId) -> Option<SysCrate> {
+    if !pkg.name().ends_with("-sys") && resolve.summary(pkg).links().is_none() {
+        return None;
+    }
+
+    let source = crate_source(src_dir, pkg)?;
+
+    Some(SysCrate {
+        name: pkg.name().to_string(),
+        version: pkg.version().to_string(),
+        features: resolve
+            .features(pkg)
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        source,
+    })
+}
+
+impl SysCrate {
+    // pkg-config modules required by the crate
+    pub(super) async fn pkg_config_probes(&self, crates_io: Option<&Client>) -> Vec<String> {
+        let files = match &self.source {
+            CrateSource::Dir(dir) => Some(CrateFiles::Dir(dir.clone())),
+            CrateSource::Archive(path) => File::open(path)
+                .ok_inspect(|e| warn!("{e}"))
+                .and_then(CrateFiles::from_archive),
+            CrateSource::CratesIo => match crates_io {
+                Some(cl) => download_crate(cl, &self.name, &self.version).await,
+                None => {
+                    info!(
+                        "the sources of {} are not available locally, use --download-crates to download them",
+                        self.name,
+                    );
+                    None
+                }
+            },
+        };
+        files.map_or_else(Vec::new, |files| crate_probes(&files, &self.features))
+    }
+}
+
+impl CrateFiles {
+    fn read(&self, path: impl AsRef<Path>) -> Option<String> {
+        match self {
+            Self::Dir(dir) => read_to_string(dir.join(path)).ok(),
+            Self::Archive(files) => files.get(path.as_ref()).cloned(),
+        }
+    }
+
+    fn from_archive(archive: impl Read) -> Option<Self> {
+        let mut files = BTreeMap::new();
+        let mut archive = Archive::new(GzDecoder::new(archive));
+        for entry in archive.entries().ok_inspect(|e| warn!("{e}"))? {
+            let Some(mut entry) = entry.ok_inspect(|e| warn!("{e}")) else {
+                continue;
+            };
+            let Ok(path) = entry.path() else {
+                continue;
+            };
+            // strip the {name}-{version} directory
+            let path: PathBuf = path.components().skip(1).collect();
+            if path != Path::new("Cargo.toml") && path.extension().is_none_or(|ext| ext != "rs") {
+                continue;
+            }
+            let mut content = String::new();
+            if entry.read_to_string(&mut content).is_ok() {
+                files.insert(path, content);
+            }
+        }
+        Some(Self::Archive(files))
+    }
+}
+
+async fn download_crate(cl: &Client, name: &str, version: &str) -> Option<CrateFiles> {
+    let url = format!("https://static.crates.io/crates/{name}/{name}-{version}.crate");
+    info!("downloading {url}");
+    let bytes = cl
+        .get(&url)
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .ok_inspect(|e| warn!("{e}"))?
+        .bytes()
+        .await
+        .ok_inspect(|e| warn!("{e}"))?;
+    CrateFiles::from_archive(&*bytes)
+}
+
+fn crate_probes(files: &CrateFiles, features: &[String]) -> Vec<String> {
+    let Some(manifest) = files
+        .read("Cargo.toml")
+        .and_then(|manifest| toml::from_str::<Manifest>(&manifest).ok_inspect(|e| warn!("{e}")))
+    else {
+        return Vec::new();
+    };
+
+    let mut modules: Vec<_> = manifest
+        .package
+        .metadata
+        .system_deps
+        .into_iter()
+        .filter_map(|(key, dep)| match dep {
+            SystemDep::Version(_) => Some(key),
+            SystemDep::Table {
+                name,
+                optional,
+                feature,
+            } => {
+                let enabled = feature.is_none_or(|feature| features.contains(&feature));
+                (!optional && enabled).then(|| name.unwrap_or(key))
+            }
+        })
+        .collect();
+
+    let build = match manifest.package.build {
+        Some(toml::Value::String(build)) => build,
+        Some(_) => return modules,
+        None => "build.rs".into(),
+    };
+    if let Some(build) = files.read(build) {
+        modules.extend(build_rs_probes(&build));
+    }
+
+    modules.sort_unstable();
+    modules.dedup();
+    modules
+}
+
+// e.g. pkg_config::probe_library("foo") or pkg_config::Config::new().probe("foo")
+fn build_rs_probes(build: &str) -> Vec<String> {
+    let Some(re) = Regex::new(r#"\bprobe(?:_library)?\(\s*"([^"]+)""#).ok_inspect(|e| warn!("{e}"))
+    else {
+        return Vec::new();
+    };
+    re.captures_iter(build).map(|caps| caps[1].into()).collect()
+}
+
+// path dependencies, vendored crates, and crates in the cargo registry,
+// crates.io as a last resort
+fn crate_source(src_dir: &Path, pkg: PackageId) -> Option<CrateSource> {
+    let src = pkg.source_id();
+    if src.is_path() {
+        return src.url().to_file_path().ok().map(CrateSource::Dir);
+    }
+
+    let dir = format!("{}-{}", pkg.name(), pkg.version());
+    let vendor = src_dir.join("vendor");
+    for path in [vendor.join(&dir), vendor.join(&*pkg.name())] {
+        if path.join("Cargo.toml").is_file() {
+            return Some(CrateSource::Dir(path));
+        }
+    }
+
+    if let Some(registry) = homedir(src_dir).map(|home| home.join("registry")) {
+        let indexes = |name| {
+            read_dir(registry.join(name))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|index| index.path())
+        };
+        if let Some(path) = indexes("src")
+            .map(|index| index.join(&dir))
+            .find(|path| path.join("Cargo.toml").is_file())
+        {
+            return Some(CrateSource::Dir(path));
+        }
+        if let Some(path) = indexes("cache")
+            .map(|index| index.join(format!("{dir}.crate")))
+            .find(|path| path.is_file())
+        {
+            return Some(CrateSource::Archive(path));
+        }
+    }
+
+    src.is_crates_io().then_some(CrateSource::CratesIo)
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compression, write::GzEncoder};
+    use indoc::indoc;
+    use tar::{Builder, Header};
+
+    use super::{CrateFiles, Manifest, SystemDep, build_rs_probes, crate_probes};
+
+    #[test]
+    fn registry_crate() {
+        let mut archive = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, content) in [
+            (
+                "foo-sys-0.1.0/Cargo.toml",
+                indoc! {r#"
+                    [package]
+                    name = "foo-sys"
+                    version = "0.1.0"
+                    build = "build/main.rs"
+
+                    [package.metadata.system-deps]
+                    libfoo = "1.2"
+                    foo_gl = { name = "foo-gl-1.0", version = "1.0", feature = "gl" }
+                    foo_vk = { name = "foo-vk-1.0", version = "1.0", feature = "vulkan" }
+                "#},
+            ),
+            (
+                "foo-sys-0.1.0/build/main.rs",
+                r#"fn main() { pkg_config::probe_library("bar").unwrap(); }"#,
+            ),
+            ("foo-sys-0.1.0/README.md", "foo"),
+        ] {
+            let mut header = Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            archive
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        let archive = archive.into_inner().unwrap().finish().unwrap();
+
+        let files = CrateFiles::from_archive(&*archive).unwrap();
+        let CrateFiles::Archive(paths) = &files else {
+            panic!("expected an archive");
+        };
+        assert_eq!(
+            Vec::from_iter(paths.keys().map(|path| path.to_str().unwrap())),
+            ["Cargo.toml", "build/main.rs"],
+        );
+        assert_eq!(
+            crate_probes(&files, &["gl".into()]),
+            ["bar", "foo-gl-1.0", "libfoo"],
+        );
+    }
+
+    #[test]
+    fn system_deps() {
+        let manifest: Manifest = toml::from_str(indoc! {r#"
+            [package]
+            name = "foo-sys"
+
+            [package.metadata.system-deps]
+            libfoo = "1.2"
+            foo_gl = { name = "foo-gl-1.0", version = "1.0", feature = "gl" }
+        "#})
+        .unwrap();
+
+        let deps = manifest.package.metadata.system_deps;
+        assert!(matches!(deps["libfoo"], SystemDep::Version(_)));
+        assert!(matches!(
+            &deps["foo_gl"],
+            SystemDep::Table { name: Some(name), optional: false, feature: Some(feature) }
+                if name == "foo-gl-1.0" && feature == "gl",
+        ));
+    }
+
+    #[test]
+    fn build_rs() {
+        assert_eq!(
+            build_rs_probes(indoc! {r#"
+                fn main() {
+                    if pkg_config::probe_library("libfoo").is_err() {
+                        pkg_config::Config::new()
+                            .atleast_version("1.0")
+                            .probe("foo-1.0")
+                            .unwrap();
+                    }
+                    vcpkg::probe_package("foo").unwrap();
+                }
+            "#}),
+            ["libfoo", "foo-1.0"],
+        );
+    }
+}
@@ -1,11 +1,14 @@
-use std::fs::{File, copy, create_dir};
+use std::fs::{File, copy, create_dir, create_dir_all, write};
 
 use heck::AsKebabCase;
 use insta::assert_debug_snapshot;
 use tempfile::tempdir;
 
-use super::resolve_workspace;
-use crate::{inputs::AllInputs, lang::rust::load_rust_dependencies};
+use super::{
+    CargoFeatures, CargoSelection, cargo_bin_members, cargo_bins, cargo_features,
+    load_selected_dependencies, resolve_workspace,
+};
+use crate::{cfg::Mappings, inputs::AllInputs, lang::rust::load_rust_dependencies};
 
 macro_rules! tests {
     ($($name:ident)+) => {
@@ -41,6 +44,203 @@ fn run(name: &str) -> AllInputs {
     File::create(src.join("lib.rs")).unwrap();
 
     let mut inputs = AllInputs::default();
-    load_rust_dependencies(&mut inputs, &resolve_workspace(dir).unwrap());
+    load_rust_dependencies(
+        &mut inputs,
+        &Mappings::default(),
+        dir,
+        &resolve_workspace(dir).unwrap(),
+    );
     inputs
 }
+
+#[test]
+fn bins() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+
+    write(
+        dir.join("Cargo.toml"),
+        r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            edition = "2024"
+
+            [[bin]]
+            name = "foo-cli"
+            path = "src/main.rs"
+        "#,
+    )
+    .unwrap();
+    create_dir_all(dir.join("src/bin")).unwrap();
+    File::create(dir.join("src/main.rs")).unwrap();
+    File::create(dir.join("src/bin/bar.rs")).unwrap();
+
+    let mut bins = cargo_bins(dir, &[]);
+    bins.sort_unstable();
+    assert_eq!(bins, ["bar", "foo-cli"]);
+}
+
+#[test]
+fn workspace() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+
+    write(
+        dir.join("Cargo.toml"),
+        r#"
+            [workspace]
+            members = ["crates/*"]
+            resolver = "3"
+        "#,
+    )
+    .unwrap();
+    for (name, target) in [("foo", "main.rs"), ("bar", "main.rs"), ("baz", "lib.rs")] {
+        let src = dir.join("crates").join(name).join("src");
+        create_dir_all(&src).unwrap();
+        write(
+            src.parent().unwrap().join("Cargo.toml"),
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2024\""),
+        )
+        .unwrap();
+        File::create(src.join(target)).unwrap();
+    }
+
+    let mut members: Vec<_> = cargo_bin_members(dir)
+        .into_iter()
+        .map(|member| (member.name, member.path.display().to_string()))
+        .collect();
+    members.sort_unstable();
+    assert_eq!(
+        members,
+        [
+            ("bar".into(), "crates/bar".into()),
+            ("foo".into(), "crates/foo".into()),
+        ],
+    );
+    assert_eq!(cargo_bins(dir, &["foo".into()]), ["foo"]);
+}
+
+#[test]
+fn features() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+
+    copy(
+        "src/lang/rust/fixtures/optional.toml",
+        dir.join("Cargo.toml"),
+    )
+    .unwrap();
+    copy(
+        "src/lang/rust/fixtures/optional-lock.toml",
+        dir.join("Cargo.lock"),
+    )
+    .unwrap();
+    let src = dir.join("src");
+    create_dir(&src).unwrap();
+    File::create(src.join("lib.rs")).unwrap();
+
+    assert_eq!(cargo_features(dir, &[]), ["llvm"]);
+    let lock = resolve_workspace(dir).unwrap();
+    let load = |features: Option<&CargoFeatures>| {
+        let mut inputs = AllInputs::default();
+        load_selected_dependencies(
+            &mut inputs,
+            &Mappings::default(),
+            dir,
+            &lock,
+            CargoSelection {
+                members: &[],
+                features,
+            },
+        );
+        inputs.build_inputs.always
+    };
+
+    assert_eq!(Vec::from_iter(load(None)), ["libxml2", "ncurses", "zlib"]);
+    assert!(load(Some(&CargoFeatures::default())).is_empty());
+    assert_eq!(
+        Vec::from_iter(load(Some(&CargoFeatures {
+            features: vec!["llvm".into()],
+            no_default_features: true,
+        }))),
+        ["libxml2", "ncurses", "zlib"],
+    );
+}
+
+#[tokio::test]
+async fn probes() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+
+    write(
+        dir.join("Cargo.toml"),
+        r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+            edition = "2024"
+
+            [dependencies]
+            foo-sys = { path = "foo-sys" }
+        "#,
+    )
+    .unwrap();
+    write(
+        dir.join("Cargo.lock"),
+        r#"
+            version = 4
+
+            [[package]]
+            name = "foo"
+            version = "0.1.0"
+            dependencies = ["foo-sys"]
+
+            [[package]]
+            name = "foo-sys"
+            version = "0.1.0"
+        "#,
+    )
+    .unwrap();
+    create_dir_all(dir.join("src")).unwrap();
+    File::create(dir.join("src/lib.rs")).unwrap();
+
+    let sys = dir.join("foo-sys");
+    create_dir_all(sys.join("src")).unwrap();
+    write(
+        sys.join("Cargo.toml"),
+        r#"
+            [package]
+            name = "foo-sys"
+            version = "0.1.0"
+            edition = "2024"
+            links = "foo"
+
+            [package.metadata.system-deps]
+            libbar = "1.0"
+        "#,
+    )
+    .unwrap();
+    write(
+        sys.join("build.rs"),
+        r#"fn main() { pkg_config::probe_library("libfoo").unwrap(); }"#,
+    )
+    .unwrap();
+    File::create(sys.join("src/lib.rs")).unwrap();
+
+    let mut inputs = AllInputs::default();
+    let sys_crates = load_rust_dependencies(
+        &mut inputs,
+        &Mappings::default(),
+        dir,
+        &resolve_workspace(dir).unwrap(),
+    );
+    let [sys_crate] = &sys_crates[..] else {
+        panic!("expected a single sys crate");
+    };
+    assert_eq!(sys_crate.name, "foo-sys");
+    assert_eq!(
+        sys_crate.pkg_config_probes(None).await,
+        ["libbar", "libfoo"]
+    );
+}
@@ -1,19 +1,22 @@
 /// define a set of macros to work with inputs
+/// `$reason` is recorded as the reason every input is added
 macro_rules! input_macros {
     // workaround to escape `$`
     // so it can be used with nested macro_rules
-    ($inputs:ident) => {
-        input_macros!(($) $inputs);
+    ($inputs:ident, $reason:expr) => {
+        input_macros!(($) $inputs, $reason);
     };
 
-    (($_:tt) $inputs:ident) => {
+    (($_:tt) $inputs:ident, $reason:expr) => {
         macro_rules! input {
             ($key:ident: $_($input:expr),+) => {
                 input!($key: $_($input),+; always)
             };
             ($key:ident: $_($input:expr),+; $sys:ident) => {{
                 $_(
-                    $inputs.$key.$sys.insert($input.into());
+                    let input: String = $input.into();
+                    $inputs.add_reason(&input, $reason);
+                    $inputs.$key.$sys.insert(input);
                 )+
             }};
         }
@@ -24,10 +27,9 @@ macro_rules! input_macros {
                 environ!($name, $value;)
             };
             ($name:expr, $value:expr; $_($tt:tt)*) => {{
-                $inputs.env.insert(
-                    $name.into(),
-                    ($value.into(), vec![$_($tt)*]),
-                );
+                let name: std::borrow::Cow<'static, str> = $name.into();
+                $inputs.add_reason(&name, $reason);
+                $inputs.env.insert(name, ($value.into(), vec![$_($tt)*]));
             }};
         }
 
@@ -1,3 +1,5 @@
+mod batch;
+mod build;
 mod cfg;
 mod cli;
 mod cmd;
@@ -8,13 +10,17 @@ mod inputs;
 mod lang;
 mod license;
 mod macros;
+mod report;
+mod update;
 mod utils;
+mod validate;
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fmt::Write as _,
     fs::{File, create_dir_all, metadata},
     io::{IsTerminal, Seek, Write as _, pipe, stderr},
+    mem,
     os::unix::ffi::OsStrExt,
     path::{Component, Path, PathBuf},
     process::Stdio,
@@ -28,27 +34,40 @@ use heck::ToKebabCase;
 use indoc::formatdoc;
 use serde::Deserialize;
 use tempfile::tempdir;
-use tokio::process::Command;
+use tokio::{process::Command, sync::Mutex};
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 use which::which;
 use zip::ZipArchive;
 
 use crate::{
-    cfg::load_config,
-    cli::{BuilderFunction, CargoVendor, Opts},
+    batch::run_batch,
+    build::{BuildFixes, MAX_BUILD_ATTEMPTS, build_package},
+    cfg::{Config, Format, load_config},
+    cli::{BuilderFunction, CargoVendor, Opts, PackageManager, ReportFormat},
     cmd::{NIX, NURL},
     codegen::{
-        BuilderDispatch, Codegen, SourceLayout, drv::MkDerivation, go::BuildGoModule,
-        npm::BuildNpmPackage, python::BuildPythonPackage, rust::BuildRustPackage,
+        BuilderCandidate, BuilderDispatch, Codegen, SourceLayout,
+        drv::MkDerivation,
+        go::BuildGoModule,
+        npm::{BuildNpmPackage, MkNodeDerivation},
+        project_roots,
+        python::BuildPythonPackage,
+        rust::BuildRustPackage,
     },
-    fetcher::{Fetcher, FetcherDispatch, PackageInfo, PypiFormat, Revisions, Version},
-    frontend::{Frontend, headless, readline},
-    lang::python::PythonDependencies,
+    fetcher::{Clients, Fetcher, FetcherDispatch, PackageInfo, PypiFormat, Revisions, Version},
+    frontend::{Frontend, FrontendDispatch, answers, headless, readline},
+    lang::{python::PythonDependencies, rust::CargoFeatures},
     license::LICENSE_STORE,
+    report::Report,
+    update::ExistingPackage,
     utils::{CommandExt, ResultExt},
 };
 
+// packages initialized concurrently by --batch are committed one at a time,
+// as git can't update the index of the same repository concurrently
+static GIT_COMMIT: Mutex<()> = Mutex::const_new(());
+
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 enum MaybeFetcher {
@@ -68,21 +87,6 @@ struct Outputs {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    run().await
-}
-
-#[derive(Default)]
-struct SourceInfo {
-    pname: Option<String>,
-    rev: String,
-    version: String,
-    description: String,
-    file_url_prefix: Option<String>,
-    releases_page: Option<String>,
-    python_dependencies: PythonDependencies,
-}
-
-async fn run() -> Result<()> {
     tracing_subscriber::fmt()
         .with_ansi(stderr().is_terminal())
         .with_env_filter(EnvFilter::from_env("NIX_INIT_LOG"))
@@ -92,30 +96,64 @@ async fn run() -> Result<()> {
         .init();
 
     let opts = Opts::parse();
-    let opt_version = match opts.version {
-        Some(Some(version)) => Some(version),
-        Some(None) => {
-            println!("nix-init {}", crate_version!());
-            return Ok(());
-        }
-        None => None,
-    };
+    if let Some(None) = opts.version {
+        println!("nix-init {}", crate_version!());
+        return Ok(());
+    }
 
     tokio::spawn(async {
         LazyLock::force(&LICENSE_STORE);
     });
 
-    let cfg = load_config(opts.config)?;
+    let mut cfg = load_config(opts.config.clone())?;
+    let clients = Clients::new(mem::take(&mut cfg.access_tokens));
+
+    if let Some(batch) = opts.batch.clone() {
+        return run_batch(opts, &batch, cfg, clients).await;
+    }
 
-    let mut frontend = if opts.headless {
+    let frontend = if let Some(path) = &opts.answers {
+        answers(path, opts.headless)?
+    } else if opts.headless {
         headless()
     } else {
         readline()?
     };
 
+    run(opts, &cfg, &Mutex::new(clients), frontend).await
+}
+
+#[derive(Default)]
+struct SourceInfo {
+    pname: Option<String>,
+    rev: String,
+    version: String,
+    description: String,
+    file_url_prefix: Option<String>,
+    releases_page: Option<String>,
+    python_dependencies: PythonDependencies,
+}
+
+async fn run(
+    opts: Opts,
+    cfg: &Config,
+    clients: &Mutex<Clients>,
+    mut frontend: FrontendDispatch,
+) -> Result<()> {
+    let opt_version = opts.version.flatten();
+
+    let existing = if opts.update {
+        Some(ExistingPackage::load(opts.output.as_deref())?)
+    } else {
+        None
+    };
+
     let mut url = match opts.url {
         Some(url) => url,
-        None => frontend.url()?,
+        None => match existing.as_ref().and_then(|pkg| pkg.url.clone()) {
+            Some(url) => url,
+            None => frontend.url()?,
+        },
     };
 
     let mut fetcher =
@@ -134,7 +172,7 @@ async fn run() -> Result<()> {
         releases_page,
         python_dependencies,
     } = if let MaybeFetcher::Known(fetcher) = &mut fetcher {
-        let cl = fetcher.create_client(cfg.access_tokens).await?;
+        let cl = clients.lock().await.get(fetcher).await?;
 
         let PackageInfo {
             pname,
@@ -164,7 +202,10 @@ async fn run() -> Result<()> {
         let submodules = match opts.submodules {
             Some(true) => fetcher.has_submodules(&cl, &rev).await,
             Some(false) => false,
-            None => fetcher.has_submodules(&cl, &rev).await && frontend.fetch_submodules()?,
+            None => match &existing {
+                Some(pkg) => pkg.fetch_submodules,
+                None => fetcher.has_submodules(&cl, &rev).await && frontend.fetch_submodules()?,
+            },
         };
         if submodules {
             cmd.arg("-S");
@@ -238,14 +279,17 @@ async fn run() -> Result<()> {
         }
     };
 
-    let pname = match opts.pname {
-        Some(rev) => rev,
+    let pname = match opts
+        .pname
+        .or_else(|| existing.as_ref().and_then(|pkg| pkg.pname.clone()))
+    {
+        Some(pname) => pname,
         None => frontend.pname(pname.map(|pname| pname.to_kebab_case()))?,
     };
 
     let nixpkgs = opts
         .nixpkgs
-        .or(cfg.nixpkgs)
+        .or_else(|| cfg.nixpkgs.clone())
         .unwrap_or_else(|| "<nixpkgs>".into());
     cmd.arg("-n").arg(&nixpkgs);
 
@@ -380,88 +424,109 @@ async fn run() -> Result<()> {
         PathBuf::from(&src)
     };
 
-    let layout = SourceLayout::detect(&src_dir);
-
-    let builder = match (opts.builder, opts.cargo_vendor) {
-        (Some(builder), rust @ Some(vendor)) if layout.has_cargo => match builder {
-            BuilderFunction::BuildGoModule => BuildGoModule.into(),
-            BuilderFunction::BuildNpmPackage => BuildNpmPackage.into(),
-            BuilderFunction::BuildPythonApplication => BuildPythonPackage::new(true, rust).into(),
-            BuilderFunction::BuildPythonPackage => BuildPythonPackage::new(false, rust).into(),
-            BuilderFunction::BuildRustPackage => BuildRustPackage::new(vendor).into(),
-            BuilderFunction::MkDerivation => MkDerivation::new(rust).into(),
-            BuilderFunction::MkDerivationNoCC => MkDerivation::no_cc().into(),
-        },
-        (Some(builder), _) => {
-            let rust = layout.has_cargo.then_some(CargoVendor::FetchCargoVendor);
-            match builder {
-                BuilderFunction::BuildGoModule => BuildGoModule.into(),
-                BuilderFunction::BuildNpmPackage => BuildNpmPackage.into(),
-                BuilderFunction::BuildPythonApplication => {
-                    BuildPythonPackage::new(true, rust).into()
-                }
-                BuilderFunction::BuildPythonPackage => BuildPythonPackage::new(false, rust).into(),
-                BuilderFunction::BuildRustPackage => {
-                    BuildRustPackage::new(CargoVendor::FetchCargoVendor).into()
-                }
-                BuilderFunction::MkDerivation => MkDerivation::new(rust).into(),
-                BuilderFunction::MkDerivationNoCC => MkDerivation::no_cc().into(),
-            }
+    let (opt_builder, opt_cargo_vendor, opt_node_package_manager) = match &existing {
+        Some(pkg) => (
+            opts.builder.or_else(|| pkg.builder.clone()),
+            opts.cargo_vendor.or(pkg.cargo_vendor),
+            opts.node_package_manager.or(pkg.node_package_manager),
+        ),
+        None => (opts.builder, opts.cargo_vendor, opts.node_package_manager),
+    };
+    let detect_layout = |dir: &Path| {
+        let mut layout = SourceLayout::detect(dir);
+        if layout.has_npm && opt_node_package_manager.is_some() {
+            layout.package_manager = opt_node_package_manager;
         }
-        (None, rust) => {
-            let mut builders = Vec::new();
-            if layout.has_go {
-                builders.push(BuildGoModule.into());
-            }
-
-            if layout.has_cargo {
-                let cargo_deps_options: &[_] = match rust {
-                    Some(vendor) => &[vendor],
-                    None => &[CargoVendor::FetchCargoVendor, CargoVendor::ImportCargoLock],
-                };
-
-                for &vendor in cargo_deps_options {
-                    if layout.has_python {
-                        for application in [true, false] {
-                            builders
-                                .push(BuildPythonPackage::new(application, Some(vendor)).into());
-                        }
-                    }
-
-                    let drv = BuilderDispatch::from(MkDerivation::new(Some(vendor)));
-                    let rust = BuilderDispatch::from(BuildRustPackage::new(vendor));
-                    builders.extend(if layout.has_meson {
-                        [drv, rust]
-                    } else {
-                        [rust, drv]
-                    });
-                }
-            }
+        layout
+    };
 
-            if layout.has_python {
-                for application in [true, false] {
-                    builders.push(BuildPythonPackage::new(application, None).into());
-                }
+    let existing_root = existing.as_ref().and_then(|pkg| pkg.root.clone());
+    let (root, builder) = if let Some(builder) = opt_builder {
+        // prefer the top level, then the first subdirectory the builder supports
+        let root = existing_root.or_else(|| {
+            if builder_supports(&builder, detect_layout(&src_dir)) {
+                None
+            } else {
+                project_roots(&src_dir)
+                    .into_iter()
+                    .find(|root| builder_supports(&builder, detect_layout(&src_dir.join(root))))
             }
+        });
+        let layout = detect_layout(&match &root {
+            Some(root) => src_dir.join(root),
+            None => src_dir.clone(),
+        });
+        (
+            root,
+            forced_builder(builder, opt_cargo_vendor, opt_node_package_manager, layout),
+        )
+    } else {
+        let roots = match existing_root {
+            Some(root) => vec![root],
+            None => project_roots(&src_dir),
+        };
 
-            if layout.has_npm {
-                builders.push(BuildNpmPackage.into());
+        // builders for the top level come first, except for the generic ones
+        let mut candidates: Vec<_> = layout_builders(detect_layout(&src_dir), opt_cargo_vendor)
+            .into_iter()
+            .map(|builder| BuilderCandidate {
+                builder,
+                root: None,
+            })
+            .collect();
+        for root in roots {
+            let layout = detect_layout(&src_dir.join(&root));
+            let mut builders = layout_builders(layout, opt_cargo_vendor);
+            if layout.has_cmake || layout.has_meson || layout.has_zig {
+                builders.push(MkDerivation::new(None).into());
             }
+            candidates.extend(builders.into_iter().map(|builder| BuilderCandidate {
+                builder,
+                root: Some(root.clone()),
+            }));
+        }
+        for builder in [MkDerivation::new(None), MkDerivation::no_cc()] {
+            candidates.push(BuilderCandidate {
+                builder: builder.into(),
+                root: None,
+            });
+        }
 
-            builders.push(MkDerivation::new(None).into());
-            builders.push(MkDerivation::no_cc().into());
+        let candidate = frontend.builder(candidates)?;
+        (candidate.root, candidate.builder)
+    };
 
-            frontend.builder(builders)?
+    let project_dir = match &root {
+        Some(root) => src_dir.join(root),
+        None => src_dir.clone(),
+    };
+    let layout = detect_layout(&project_dir);
+
+    let builder = match builder {
+        BuilderDispatch::BuildGoModule(builder) => builder
+            .detect_toolchain(&project_dir, &nixpkgs)
+            .await
+            .into(),
+        BuilderDispatch::BuildNpmPackage(builder) => {
+            builder.detect_nodejs(&project_dir, &nixpkgs).await.into()
         }
+        BuilderDispatch::MkNodeDerivation(builder) => {
+            builder.detect_nodejs(&project_dir, &nixpkgs).await.into()
+        }
+        builder => builder,
     };
 
-    let output = if let Some(output) = opts.output {
+    let output = if let Some(pkg) = &existing {
+        pkg.path.clone()
+    } else if let Some(output) = opts.output {
         output
     } else {
         frontend.output(&pname, &builder)?
     };
 
-    let (out_dir, out_path) = if let Ok(metadata) = metadata(&output) {
+    let (out_dir, out_path) = if existing.is_some() {
+        (output.parent(), output.clone())
+    } else if let Ok(metadata) = metadata(&output) {
         if metadata.is_dir() {
             let out_path = output.join("default.nix");
             if out_path.exists() && !frontend.should_overwrite(&out_path, opts.overwrite)? {
@@ -489,48 +554,81 @@ async fn run() -> Result<()> {
         MaybeFetcher::Known(fetcher) => fetcher.to_string(),
         MaybeFetcher::Unknown { fetcher } => fetcher,
     };
-    let cg = Codegen {
-        description,
-        fetcher_input,
-        file_url_prefix,
-        frontend: &mut frontend,
-        inputs: Default::default(),
-        layout,
-        licenses,
-        maintainers: &cfg.maintainers,
-        nix_update_script,
-        nixpkgs: &nixpkgs,
-        out: String::new(),
-        out_dir,
-        overwrite: opts.overwrite,
-        pname: &pname,
-        python_deps: python_dependencies,
-        releases_page,
-        src: &src,
-        src_dir: &src_dir,
-        src_expr: &src_expr,
-        url: &url,
-        version: &version,
+    let mut fixes = BuildFixes::new(existing.is_some());
+    let mut report = None;
+    // answers to the prompts and prefetched hashes are kept across the attempts of --build
+    let mut cargo_features =
+        (opts.features.is_some() || opts.no_default_features).then(|| CargoFeatures {
+            features: opts.features.clone().unwrap_or_default(),
+            no_default_features: opts.no_default_features,
+        });
+    let mut cargo_members = None;
+    let mut main_program = None;
+    let mut hashes = BTreeMap::new();
+    let crates_io = if opts.download_crates.unwrap_or(cfg.download_crates) {
+        Some(clients.lock().await.crates_io()?)
+    } else {
+        None
     };
-    let out = cg.generate(builder).await?;
-
-    let mut out_file = File::create(&out_path).context("failed to create output file")?;
-    if let Some(fmt) = cfg.format {
-        let mut args = fmt.command.into_iter();
-        if let Some(cmd) = args.next() {
-            let mut cmd = Command::new(cmd);
-            cmd.args(args);
-            maybe_format(&out, out_file, cmd).await?;
-        } else {
-            error!("format.command should contain at least 1 element");
-            write!(out_file, "{out}")?;
+    for attempt in 1 ..= MAX_BUILD_ATTEMPTS {
+        let mut cg = Codegen {
+            cargo_features: cargo_features.take(),
+            cargo_members: cargo_members.take(),
+            crates_io: crates_io.clone(),
+            description: description.clone(),
+            explain: opts.explain.unwrap_or(cfg.explain),
+            extra_python_deps: &fixes.python_deps,
+            fetcher_input: fetcher_input.clone(),
+            file_url_prefix: file_url_prefix.clone(),
+            frontend: &mut frontend,
+            hashes: mem::take(&mut hashes),
+            inputs: fixes.inputs.clone(),
+            layout,
+            licenses: licenses.clone(),
+            main_program: main_program.take(),
+            maintainers: &cfg.maintainers,
+            mappings: &cfg.mappings,
+            missing_inputs: BTreeSet::new(),
+            nix_update_script,
+            nixpkgs: &nixpkgs,
+            out: String::new(),
+            out_dir,
+            // lock files next to the package are regenerated along with the package itself
+            overwrite: opts.overwrite.or(existing.is_some().then_some(true)),
+            pname: &pname,
+            python_deps: python_dependencies.clone(),
+            releases_page: releases_page.clone(),
+            src: &src,
+            project_dir: &project_dir,
+            root: root.as_deref(),
+            src_dir: &src_dir,
+            src_expr: &src_expr,
+            url: &url,
+            version: &version,
+        };
+        let out = fixes.apply_hashes(cg.generate(&builder).await?);
+        if opts.report.is_some() {
+            report = Some(Report::new(&cg, &builder, &rev, &fixes));
+        }
+        cargo_features = cg.cargo_features;
+        cargo_members = cg.cargo_members;
+        main_program = cg.main_program;
+        hashes = cg.hashes;
+        write_output(&out_path, &out, existing.as_ref(), cfg.format.as_ref()).await?;
+
+        if !opts.build || !build_package(&mut fixes, &nixpkgs, &out_path, &builder).await? {
+            break;
+        }
+        if attempt == MAX_BUILD_ATTEMPTS {
+            error!(
+                "{} still fails to build after {MAX_BUILD_ATTEMPTS} attempts",
+                out_path.display(),
+            );
         }
-    } else if which("nixfmt").is_ok() {
-        let mut cmd = Command::new("nixfmt");
-        cmd.arg("-");
-        maybe_format(&out, out_file, cmd).await?;
-    } else {
-        write!(out_file, "{out}")?;
+    }
+
+    if let (Some(ReportFormat::Json), Some(report)) = (opts.report, report) {
+        println!("{}", serde_json::to_string_pretty(&report)?);
     }
 
     if !opts.commit.unwrap_or(cfg.commit) || !Path::new(".git").is_dir() {
@@ -562,6 +660,7 @@ async fn run() -> Result<()> {
         _ => return Ok(()),
     };
 
+    let _guard = GIT_COMMIT.lock().await;
     Command::new("git")
         .arg("add")
         .arg("-N")
@@ -569,12 +668,19 @@ async fn run() -> Result<()> {
         .run()
         .await?;
 
-    let msg = formatdoc! {r#"
-      {attr}: init at {version}
+    let msg = match existing.and_then(|pkg| pkg.version) {
+        Some(old) => formatdoc! {r#"
+          {attr}: {old} -> {version}
+
+          Assisted-by: nix-init"#,
+        },
+        None => formatdoc! {r#"
+          {attr}: init at {version}
 
-      {url}
+          {url}
 
-      Assisted-by: nix-init"#,
+          Assisted-by: nix-init"#,
+        },
     };
     Command::new("git")
         .arg("commit")
@@ -587,6 +693,107 @@ async fn run() -> Result<()> {
     Ok(())
 }
 
+fn builder_supports(builder: &BuilderFunction, layout: SourceLayout) -> bool {
+    match builder {
+        BuilderFunction::BuildGoModule => layout.has_go,
+        BuilderFunction::BuildNpmPackage => layout.has_npm,
+        BuilderFunction::BuildPythonApplication | BuilderFunction::BuildPythonPackage => {
+            layout.has_python
+        }
+        BuilderFunction::BuildRustPackage => layout.has_cargo,
+        BuilderFunction::MkDerivation | BuilderFunction::MkDerivationNoCC => false,
+    }
+}
+
+fn forced_builder(
+    builder: BuilderFunction,
+    cargo_vendor: Option<CargoVendor>,
+    node_package_manager: Option<PackageManager>,
+    layout: SourceLayout,
+) -> BuilderDispatch {
+    match (builder, cargo_vendor) {
+        (builder, rust @ Some(vendor)) if layout.has_cargo => match builder {
+            BuilderFunction::BuildGoModule => BuildGoModule::default().into(),
+            BuilderFunction::BuildNpmPackage => BuildNpmPackage::default().into(),
+            BuilderFunction::BuildPythonApplication => BuildPythonPackage::new(true, rust).into(),
+            BuilderFunction::BuildPythonPackage => BuildPythonPackage::new(false, rust).into(),
+            BuilderFunction::BuildRustPackage => BuildRustPackage::new(vendor).into(),
+            BuilderFunction::MkDerivation => MkDerivation::new(rust).into(),
+            BuilderFunction::MkDerivationNoCC => MkDerivation::no_cc().into(),
+        },
+        (builder, _) => {
+            let rust = layout.has_cargo.then_some(CargoVendor::FetchCargoVendor);
+            match builder {
+                BuilderFunction::BuildGoModule => BuildGoModule::default().into(),
+                BuilderFunction::BuildNpmPackage => BuildNpmPackage::default().into(),
+                BuilderFunction::BuildPythonApplication => {
+                    BuildPythonPackage::new(true, rust).into()
+                }
+                BuilderFunction::BuildPythonPackage => BuildPythonPackage::new(false, rust).into(),
+                BuilderFunction::BuildRustPackage => {
+                    BuildRustPackage::new(CargoVendor::FetchCargoVendor).into()
+                }
+                BuilderFunction::MkDerivation => match node_package_manager {
+                    Some(manager) if layout.has_npm && rust.is_none() => {
+                        MkNodeDerivation::new(manager).into()
+                    }
+                    _ => MkDerivation::new(rust).into(),
+                },
+                BuilderFunction::MkDerivationNoCC => MkDerivation::no_cc().into(),
+            }
+        }
+    }
+}
+
+// builders suited for the layout, excluding the generic mkDerivation
+fn layout_builders(layout: SourceLayout, rust: Option<CargoVendor>) -> Vec<BuilderDispatch> {
+    let mut builders = Vec::new();
+    if layout.has_go {
+        builders.push(BuildGoModule::default().into());
+    }
+
+    if layout.has_cargo {
+        let cargo_deps_options: &[_] = match rust {
+            Some(vendor) => &[vendor],
+            None => &[CargoVendor::FetchCargoVendor, CargoVendor::ImportCargoLock],
+        };
+
+        for &vendor in cargo_deps_options {
+            if layout.has_python {
+                for application in [true, false] {
+                    builders.push(BuildPythonPackage::new(application, Some(vendor)).into());
+                }
+            }
+
+            let drv = BuilderDispatch::from(MkDerivation::new(Some(vendor)));
+            let rust = BuilderDispatch::from(BuildRustPackage::new(vendor));
+            builders.extend(if layout.has_meson {
+                [drv, rust]
+            } else {
+                [rust, drv]
+            });
+        }
+    }
+
+    if layout.has_python {
+        for application in [true, false] {
+            builders.push(BuildPythonPackage::new(application, None).into());
+        }
+    }
+
+    if layout.has_npm {
+        if let Some(manager) = layout.package_manager {
+            builders.push(MkNodeDerivation::new(manager).into());
+        }
+        // buildNpmPackage can't use the lockfiles of other package managers
+        if layout.has_npm_lock || layout.package_manager.is_none() {
+            builders.push(BuildNpmPackage::default().into());
+        }
+    }
+
+    builders
+}
+
 fn get_version(rev: &str) -> &str {
     if rev.len() == 40 {
         "unstable"
@@ -599,6 +806,36 @@ fn get_version_number(rev: &str) -> &str {
     &rev[rev.find(char::is_numeric).unwrap_or_default() ..]
 }
 
+async fn write_output(
+    out_path: &Path,
+    out: &str,
+    existing: Option<&ExistingPackage>,
+    format: Option<&Format>,
+) -> Result<()> {
+    let mut out_file = File::create(out_path).context("failed to create output file")?;
+    if let Some(pkg) = existing {
+        // hand-edited packages are left as is apart from the updated attributes
+        write!(out_file, "{}", pkg.merge(out))?;
+    } else if let Some(fmt) = format {
+        let mut args = fmt.command.iter();
+        if let Some(cmd) = args.next() {
+            let mut cmd = Command::new(cmd);
+            cmd.args(args);
+            maybe_format(out, out_file, cmd).await?;
+        } else {
+            error!("format.command should contain at least 1 element");
+            write!(out_file, "{out}")?;
+        }
+    } else if which("nixfmt").is_ok() {
+        let mut cmd = Command::new("nixfmt");
+        cmd.arg("-");
+        maybe_format(out, out_file, cmd).await?;
+    } else {
+        write!(out_file, "{out}")?;
+    }
+    Ok(())
+}
+
 async fn maybe_format(content: &str, mut file: File, cmd: Command) -> Result<()> {
     if let Err(e) = try_format(content, &file, cmd).await {
         error!("{e}");
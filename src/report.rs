@@ -0,0 +1,116 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    mem,
+};
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    build::BuildFixes,
+    codegen::{Builder, BuilderDispatch, Codegen, SourceLayout},
+    inputs::AllInputs,
+    lang::python::PythonDependencies,
+};
+
+// everything nix-init inferred about a package, for tools wrapping nix-init
+#[derive(Serialize)]
+pub struct Report {
+    source: Source,
+    layout: SourceLayout,
+    builder: ReportBuilder,
+    licenses: BTreeMap<&'static str, f32>,
+    inputs: AllInputs,
+    missing_inputs: BTreeSet<String>,
+    python_dependencies: PythonDependencies,
+    hashes: BTreeMap<&'static str, String>,
+}
+
+#[derive(Serialize)]
+struct Source {
+    url: String,
+    fetcher: String,
+    rev: String,
+    pname: String,
+    version: String,
+    description: String,
+    file_url_prefix: Option<String>,
+    releases_page: Option<String>,
+    store_path: String,
+}
+
+#[derive(Serialize)]
+struct ReportBuilder {
+    name: String,
+    function: Cow<'static, str>,
+    root: Option<String>,
+}
+
+impl Report {
+    pub fn new(cg: &Codegen<'_>, builder: &BuilderDispatch, rev: &str, fixes: &BuildFixes) -> Self {
+        let mut hashes = cg.hashes.clone();
+        if let Some(hash) = src_hash(cg.src_expr) {
+            hashes.insert("src", hash);
+        }
+        // hashes fixed by --build are not known to the codegen
+        for hash in hashes.values_mut() {
+            *hash = fixes.apply_hashes(mem::take(hash));
+        }
+
+        Self {
+            source: Source {
+                url: cg.url.into(),
+                fetcher: cg.fetcher_input.clone(),
+                rev: rev.into(),
+                pname: cg.pname.into(),
+                version: cg.version.into(),
+                description: cg.description.clone(),
+                file_url_prefix: cg.file_url_prefix.clone(),
+                releases_page: cg.releases_page.clone(),
+                store_path: cg.src.into(),
+            },
+            layout: cg.layout,
+            builder: ReportBuilder {
+                name: builder.to_string(),
+                function: builder.function(),
+                root: cg.root.map(Into::into),
+            },
+            licenses: cg.licenses.clone(),
+            inputs: cg.inputs.clone(),
+            missing_inputs: cg.missing_inputs.clone(),
+            python_dependencies: cg.python_deps.clone(),
+            hashes,
+        }
+    }
+}
+
+fn src_hash(src_expr: &str) -> Option<String> {
+    Regex::new(r#"\bhash\s*=\s*"([^"]+)""#)
+        .ok()?
+        .captures(src_expr)
+        .map(|caps| caps[1].into())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::src_hash;
+
+    #[test]
+    fn src() {
+        assert_eq!(
+            src_hash(indoc! {r#"
+                fetchFromGitHub {
+                    owner = "nix-community";
+                    repo = "nix-init";
+                    tag = "v${finalAttrs.version}";
+                    hash = "sha256-Oe/VHHe8d7BKyvmqBjvv4yxLiQUvcz+6Ei7kV8VRFRk=";
+                  }"#})
+            .as_deref(),
+            Some("sha256-Oe/VHHe8d7BKyvmqBjvv4yxLiQUvcz+6Ei7kV8VRFRk="),
+        );
+        assert_eq!(src_hash("fetchgit { }"), None);
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    fs::read_to_string,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use regex::Regex;
+
+use crate::cli::{BuilderFunction, CargoVendor, PackageManager};
+
+// attributes that get regenerated in update mode
+// each entry is an optional enclosing attribute set and a pattern for the attribute names
+const UPDATED_ATTRS: [(Option<&str>, &str); 11] = [
+    (None, "version"),
+    (Some("src"), "rev|tag"),
+    (Some("src"), "hash"),
+    (Some("cargoDeps"), "hash"),
+    (None, "cargoHash"),
+    (None, "vendorHash"),
+    (None, "npmDepsHash"),
+    (Some("pnpmDeps"), "hash"),
+    (Some("yarnOfflineCache"), "hash"),
+    (Some("offlineCache"), "hash"),
+    (Some("node_modules"), "outputHash"),
+];
+
+pub struct ExistingPackage {
+    pub path: PathBuf,
+    content: String,
+    pub pname: Option<String>,
+    pub version: Option<String>,
+    // the url of the source, parsed from the fetcher of src
+    pub url: Option<String>,
+    pub builder: Option<BuilderFunction>,
+    pub cargo_vendor: Option<CargoVendor>,
+    pub node_package_manager: Option<PackageManager>,
+    pub fetch_submodules: bool,
+    // subdirectory of the source containing the project
+    pub root: Option<String>,
+}
+
+impl ExistingPackage {
+    pub fn load(output: Option<&Path>) -> Result<Self> {
+        let Some(output) = output else {
+            bail!("specifying the path to an existing package is required in update mode");
+        };
+
+        let path = if output.is_dir() {
+            ["package.nix", "default.nix"]
+                .into_iter()
+                .map(|name| output.join(name))
+                .find(|path| path.is_file())
+                .with_context(|| format!("no package.nix or default.nix in {}", output.display()))?
+        } else {
+            output.into()
+        };
+
+        let content =
+            read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+
+        let builder = Regex::new(r"(?m)^([\w.]+)\s+(?:\(\s*finalAttrs\s*:|rec\b)")?
+            .captures(&content)
+            .and_then(|caps| parse_builder(&caps[1]));
+
+        let cargo_vendor = if content.contains("cargoLock") || content.contains("importCargoLock") {
+            Some(CargoVendor::ImportCargoLock)
+        } else if content.contains("cargoHash") || content.contains("fetchCargoVendor") {
+            Some(CargoVendor::FetchCargoVendor)
+        } else {
+            None
+        };
+
+        // the fetchers of the node dependencies next to stdenv.mkDerivation
+        let node_package_manager = if content.contains("pnpmDeps") {
+            Some(PackageManager::Pnpm)
+        } else if content.contains("fetchYarnBerryDeps") {
+            Some(PackageManager::YarnBerry)
+        } else if content.contains("yarnOfflineCache") {
+            Some(PackageManager::Yarn)
+        } else if content.contains("node_modules = stdenvNoCC.mkDerivation") {
+            Some(PackageManager::Bun)
+        } else {
+            None
+        };
+
+        let pname = get_string(&content, "pname");
+        Ok(Self {
+            url: src_url(&content, pname.as_deref()),
+            pname,
+            version: get_string(&content, "version"),
+            builder,
+            cargo_vendor,
+            node_package_manager,
+            fetch_submodules: Regex::new(r"\bfetchSubmodules\s*=\s*true\b")?.is_match(&content),
+            root: get_string(&content, "modRoot").or_else(|| {
+                get_string(&content, "sourceRoot")?
+                    .strip_prefix("${finalAttrs.src.name}/")
+                    .map(Into::into)
+            }),
+            path,
+            content,
+        })
+    }
+
+    // replace the regenerated attributes with the ones from the newly generated package
+    pub fn merge(&self, generated: &str) -> String {
+        let mut content = self.content.clone();
+        for (parent, name) in UPDATED_ATTRS {
+            if let (Some(old), Some(new)) = (
+                find_attr(&content, parent, name),
+                find_attr(generated, parent, name),
+            ) {
+                content.replace_range(old, &generated[new]);
+            }
+        }
+        content
+    }
+}
+
+fn parse_builder(function: &str) -> Option<BuilderFunction> {
+    // versioned builders, e.g. buildGo124Module
+    if function
+        .strip_prefix("buildGo")
+        .and_then(|version| version.strip_suffix("Module"))
+        .is_some_and(|version| !version.is_empty() && version.bytes().all(|c| c.is_ascii_digit()))
+    {
+        return Some(BuilderFunction::BuildGoModule);
+    }
+
+    BuilderFunction::from_str(function, false)
+        .or_else(|_| {
+            BuilderFunction::from_str(
+                function.rsplit_once('.').map_or(function, |(_, name)| name),
+                false,
+            )
+        })
+        .ok()
+}
+
+// the url nurl would generate the src fetcher from
+fn src_url(content: &str, pname: Option<&str>) -> Option<String> {
+    let fetcher = Regex::new(r"(?m)^\s*src\s*=\s*([\w.-]+)\s*\{")
+        .ok()?
+        .captures(content)?
+        .get(1)?
+        .as_str();
+    let get = |name| get_block_string(content, Some("src"), name);
+    // `inherit (finalAttrs) pname;` is common for fetchCrate and fetchPypi
+    let pname = || get("pname").or_else(|| pname.map(Into::into));
+
+    Some(match fetcher.rsplit('.').next()? {
+        "fetchFromGitHub" => format!(
+            "{}/{}/{}",
+            get("githubBase").map_or_else(
+                || "https://github.com".into(),
+                |base| format!("https://{base}")
+            ),
+            get("owner")?,
+            get("repo")?,
+        ),
+        "fetchFromGitLab" => format!(
+            "https://{}/{}/{}",
+            get("domain").as_deref().unwrap_or("gitlab.com"),
+            get("owner")?,
+            get("repo")?,
+        ),
+        "fetchFromGitea" | "fetchFromForgejo" => {
+            format!(
+                "https://{}/{}/{}",
+                get("domain")?,
+                get("owner")?,
+                get("repo")?
+            )
+        }
+        "fetchFromCodeberg" => format!("https://codeberg.org/{}/{}", get("owner")?, get("repo")?),
+        "fetchFromSourcehut" => format!("https://git.sr.ht/{}/{}", get("owner")?, get("repo")?),
+        "fetchCrate" => format!("https://crates.io/crates/{}", pname()?),
+        "fetchPypi" => format!("https://pypi.org/project/{}", pname()?),
+        _ => get("url").filter(|url| !url.contains("${"))?,
+    })
+}
+
+fn get_string(content: &str, name: &str) -> Option<String> {
+    get_block_string(content, None, name)
+}
+
+fn get_block_string(content: &str, parent: Option<&str>, name: &str) -> Option<String> {
+    let range = find_attr(content, parent, name)?;
+    let (_, value) = content[range].split_once('=')?;
+    Some(
+        value
+            .trim()
+            .strip_suffix(';')?
+            .trim()
+            .strip_prefix('"')?
+            .strip_suffix('"')?
+            .into(),
+    )
+}
+
+// finds the first `name = value;` assignment
+// either inside the attribute set assigned to `parent`, or directly in the derivation
+fn find_attr(content: &str, parent: Option<&str>, name: &str) -> Option<Range<usize>> {
+    let re = Regex::new(&format!(r#"\b(?:{name})\s*=\s*(?:"[^"]*"|null)\s*;"#)).ok()?;
+
+    if let Some(parent) = parent {
+        let range = find_block(content, parent)?;
+        let m = re.find(&content[range.clone()])?;
+        return Some(range.start + m.start() .. range.start + m.end());
+    }
+
+    // the attribute set of the derivation is the only one at depth 1,
+    // the arguments of the function are closed before it
+    re.find_iter(content)
+        .find(|m| {
+            let before = &content[.. m.start()];
+            before.matches('{').count() == before.matches('}').count() + 1
+        })
+        .map(|m| m.range())
+}
+
+fn find_block(content: &str, parent: &str) -> Option<Range<usize>> {
+    let m = Regex::new(&format!(r"(?m)^\s*{parent}\s*=[^{{;]*\{{"))
+        .ok()?
+        .find(content)?;
+
+    let mut depth = 0;
+    for (i, c) in content[m.end() ..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(m.end() .. m.end() + i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::{ExistingPackage, get_string, parse_builder, src_url};
+    use crate::cli::{BuilderFunction, CargoVendor};
+
+    const OLD: &str = indoc! {r#"
+        rustPlatform.buildRustPackage (finalAttrs: {
+          pname = "foo";
+          version = "0.1.0";
+
+          src = fetchFromGitHub {
+            owner = "bar";
+            repo = "foo";
+            rev = "0000000000000000000000000000000000000000";
+            hash = "sha256-old";
+          };
+
+          cargoHash = "sha256-old-cargo";
+
+          # hand-edited
+          buildFeatures = [ "baz" ];
+
+          meta.homepage = "https://github.com/bar/foo";
+        })
+    "#};
+
+    #[test]
+    fn load() {
+        assert_eq!(get_string(OLD, "pname").as_deref(), Some("foo"));
+        assert_eq!(get_string(OLD, "version").as_deref(), Some("0.1.0"));
+        assert_eq!(
+            src_url(OLD, Some("foo")).as_deref(),
+            Some("https://github.com/bar/foo"),
+        );
+        assert!(matches!(
+            parse_builder("rustPlatform.buildRustPackage"),
+            Some(BuilderFunction::BuildRustPackage),
+        ));
+        assert!(matches!(
+            parse_builder("stdenvNoCC.mkDerivation"),
+            Some(BuilderFunction::MkDerivationNoCC),
+        ));
+        assert!(matches!(
+            parse_builder("buildGo124Module"),
+            Some(BuilderFunction::BuildGoModule),
+        ));
+    }
+
+    #[test]
+    fn merge() {
+        let pkg = ExistingPackage {
+            path: "package.nix".into(),
+            content: OLD.into(),
+            pname: None,
+            version: None,
+            url: None,
+            builder: None,
+            cargo_vendor: Some(CargoVendor::FetchCargoVendor),
+            node_package_manager: None,
+            fetch_submodules: false,
+            root: None,
+        };
+
+        let new = pkg.merge(indoc! {r#"
+            rustPlatform.buildRustPackage (finalAttrs: {
+              pname = "foo";
+              version = "0.2.0";
+              __structuredAttrs = true;
+
+              src = fetchFromGitHub {
+                owner = "bar";
+                repo = "foo";
+                tag = "v${finalAttrs.version}";
+                hash = "sha256-new";
+              };
+
+              cargoHash = "sha256-new-cargo";
+            })
+        "#});
+
+        assert_eq!(
+            new,
+            indoc! {r#"
+                rustPlatform.buildRustPackage (finalAttrs: {
+                  pname = "foo";
+                  version = "0.2.0";
+
+                  src = fetchFromGitHub {
+                    owner = "bar";
+                    repo = "foo";
+                    tag = "v${finalAttrs.version}";
+                    hash = "sha256-new";
+                  };
+
+                  cargoHash = "sha256-new-cargo";
+
+                  # hand-edited
+                  buildFeatures = [ "baz" ];
+
+                  meta.homepage = "https://github.com/bar/foo";
+                })
+            "#},
+        );
+    }
+
+    #[test]
+    fn src() {
+        assert_eq!(
+            src_url(
+                indoc! {r#"
+                    {
+                      lib,
+                      python3Packages,
+                      fetchPypi,
+                    }:
+
+                    python3Packages.buildPythonApplication (finalAttrs: {
+                      pname = "foo";
+                      version = "1.0.0";
+
+                      src = fetchPypi {
+                        inherit (finalAttrs) pname version;
+                        hash = "sha256-old";
+                      };
+
+                      meta.homepage = "https://foo.readthedocs.io";
+                    })
+                "#},
+                Some("foo"),
+            )
+            .as_deref(),
+            Some("https://pypi.org/project/foo"),
+        );
+
+        assert_eq!(
+            src_url(
+                indoc! {r#"
+                    src = fetchFromGitLab {
+                      domain = "gitlab.gnome.org";
+                      owner = "World";
+                      repo = "foo";
+                    };
+                "#},
+                None,
+            )
+            .as_deref(),
+            Some("https://gitlab.gnome.org/World/foo"),
+        );
+
+        assert_eq!(
+            src_url(
+                indoc! {r#"
+                    src = fetchurl {
+                      url = "https://example.com/foo-${finalAttrs.version}.tar.gz";
+                    };
+                "#},
+                None,
+            ),
+            None,
+        );
+    }
+
+    #[test]
+    fn top_level() {
+        let content = indoc! {r#"
+            stdenv.mkDerivation (finalAttrs: {
+              pname = "foo";
+
+              pnpmDeps = pnpm.fetchDeps {
+                version = "0.0.0";
+                hash = "sha256-old";
+              };
+
+              version = "1.0.0";
+            })
+        "#};
+        assert_eq!(get_string(content, "version").as_deref(), Some("1.0.0"));
+    }
+}
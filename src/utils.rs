@@ -1,8 +1,15 @@
-use std::{future::Future, io::BufRead, path::Path, pin::Pin, process::Output};
+use std::{
+    future::Future,
+    io::BufRead,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::Output,
+};
 
 use anyhow::{Result, bail};
 use tokio::process::Command;
 use tracing::{info, warn};
+use xdg::BaseDirectories;
 
 use crate::{cmd::NIX, codegen::Builder};
 
@@ -122,6 +129,29 @@ pub async fn fod_hash(expr: String) -> Option<String> {
     }
 }
 
+// a file in the cache directory specific to the nixpkgs in the nix store
+pub async fn nixpkgs_cache_path(nixpkgs: &str, dir: &str) -> Option<PathBuf> {
+    let stdout = Command::new(NIX)
+        .arg("eval")
+        .arg("--extra-experimental-features")
+        .arg("nix-command")
+        .arg("--impure")
+        .arg("--raw")
+        .arg("--expr")
+        .arg(format!("toString(import({nixpkgs}){{}}).path"))
+        .get_stdout()
+        .await
+        .ok_inspect(|e| warn!("{e}"))?;
+
+    let path = String::from_utf8(stdout).ok_inspect(|e| warn!("{e}"))?;
+    // local checkouts can change without their path changing
+    let name = path.strip_prefix("/nix/store/")?.replace('/', "-");
+
+    BaseDirectories::with_prefix("nix-init")
+        .get_cache_home()
+        .map(|cache| cache.join(dir).join(format!("{name}.json")))
+}
+
 pub fn by_name_path(pname: &str, builder: &impl Builder) -> Option<String> {
     if !builder.allow_by_name() {
         return None;
@@ -0,0 +1,248 @@
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::{
+    cmd::NIX,
+    codegen::Codegen,
+    inputs::AllInputs,
+    utils::{CommandExt, ResultExt},
+};
+
+// checks that all the inputs and python dependencies exist in nixpkgs with a single evaluation
+// inputs that only exist under a different name are renamed,
+// the rest are added to `Codegen::missing_inputs`
+// python dependencies are relaxed if their versions in nixpkgs don't satisfy the requirements
+// `scope` is the package set callPackage is called from, e.g. python3Packages
+pub async fn validate_inputs(cg: &mut Codegen<'_>, scope: Option<&str>) {
+    let inputs = input_names(&cg.inputs);
+    let python_deps: BTreeSet<_> = cg.python_deps.names().cloned().collect();
+
+    let mut attrs = BTreeSet::new();
+    for input in &inputs {
+        for name in candidates(input) {
+            attrs.extend(scoped(scope, &name));
+        }
+    }
+    for dep in &python_deps {
+        for name in candidates(dep) {
+            attrs.insert(format!("python3Packages.{name}"));
+        }
+    }
+
+    // nothing to check, so don't evaluate nixpkgs
+    if attrs.is_empty() {
+        return;
+    }
+
+    let Some(found) = existing_attrs(cg.nixpkgs, &attrs).await else {
+        return;
+    };
+
+    for input in inputs {
+        match candidates(&input)
+            .into_iter()
+            .find(|name| scoped(scope, name).any(|attr| found.contains_key(&attr)))
+        {
+            Some(name) if name == input => {}
+            Some(name) => {
+                info!("renaming input {input} to {name}");
+                rename_input(&mut cg.inputs, &input, name);
+            }
+            None => {
+                warn!("{input} was not found in nixpkgs");
+                cg.missing_inputs.insert(input);
+            }
+        }
+    }
+
+    for dep in python_deps {
+        match candidates(&dep)
+            .into_iter()
+            .find(|name| found.contains_key(&format!("python3Packages.{name}")))
+        {
+            Some(name) if name == dep => {}
+            Some(name) => {
+                info!("renaming python dependency {dep} to {name}");
+                cg.python_deps.rename(&dep, &name);
+            }
+            None => {
+                warn!("python3Packages.{dep} was not found in nixpkgs");
+                cg.missing_inputs.insert(format!("python3Packages.{dep}"));
+            }
+        }
+    }
+
+    cg.python_deps.relax_constraints(|name| {
+        found
+            .get(&format!("python3Packages.{name}"))
+            .and_then(Option::as_deref)
+    });
+}
+
+fn input_names(inputs: &AllInputs) -> BTreeSet<String> {
+    let AllInputs {
+        native_build_inputs,
+        build_inputs,
+        env,
+        ..
+    } = inputs;
+
+    [native_build_inputs, build_inputs]
+        .into_iter()
+        .flat_map(|inputs| {
+            [
+                &inputs.always,
+                &inputs.darwin,
+                &inputs.aarch64_darwin,
+                &inputs.x86_64_darwin,
+                &inputs.linux,
+                &inputs.aarch64_linux,
+                &inputs.x86_64_linux,
+            ]
+        })
+        .flatten()
+        .chain(env.values().flat_map(|(_, inputs)| inputs))
+        .cloned()
+        .collect()
+}
+
+fn rename_input(inputs: &mut AllInputs, old: &str, new: String) {
+    for inputs in [&mut inputs.native_build_inputs, &mut inputs.build_inputs] {
+        for inputs in [
+            &mut inputs.always,
+            &mut inputs.darwin,
+            &mut inputs.aarch64_darwin,
+            &mut inputs.x86_64_darwin,
+            &mut inputs.linux,
+            &mut inputs.aarch64_linux,
+            &mut inputs.x86_64_linux,
+        ] {
+            if inputs.remove(old) {
+                inputs.insert(new.clone());
+            }
+        }
+    }
+
+    for (_, inputs) in inputs.env.values_mut() {
+        for input in inputs {
+            if input == old {
+                input.clone_from(&new);
+            }
+        }
+    }
+
+    if let Some(reasons) = inputs.reasons.remove(old) {
+        inputs.reasons.entry(new).or_default().extend(reasons);
+    }
+}
+
+// the attribute paths an input could refer to
+fn scoped<'a>(scope: Option<&'a str>, name: &'a str) -> impl Iterator<Item = String> + 'a {
+    scope
+        .map(|scope| format!("{scope}.{name}"))
+        .into_iter()
+        .chain([name.into()])
+}
+
+// the name itself followed by normalised versions of it
+fn candidates(name: &str) -> Vec<String> {
+    let (parent, attr) = match name.rsplit_once('.') {
+        Some((parent, attr)) => (Some(parent), attr),
+        None => (None, name),
+    };
+
+    let lower = attr.to_lowercase();
+    let mut attrs = vec![
+        attr.into(),
+        lower.replace('_', "-"),
+        lower.replace('-', "_"),
+        lower.clone(),
+    ];
+    if let Some(alias) = alias(&lower) {
+        attrs.push(alias.into());
+    }
+    if let Some(attr) = lower.strip_prefix("python-") {
+        attrs.push(attr.into());
+    }
+
+    attrs
+        .into_iter()
+        .unique()
+        .map(|attr| match parent {
+            Some(parent) => format!("{parent}.{attr}"),
+            None => attr,
+        })
+        .collect()
+}
+
+// names that differ from their attribute names in nixpkgs
+fn alias(name: &str) -> Option<&'static str> {
+    Some(match name {
+        // keep-sorted start
+        "faiss-cpu" | "faiss-gpu" => "faiss",
+        "msgpack-python" => "msgpack",
+        "onnxruntime-gpu" => "onnxruntime",
+        "opencv-contrib-python" | "opencv-python" | "opencv-python-headless" => "opencv4",
+        "psycopg-binary" => "psycopg",
+        "psycopg2-binary" => "psycopg2",
+        "tensorflow-cpu" | "tensorflow-gpu" => "tensorflow",
+        "torch-cpu" => "torch",
+        // keep-sorted end
+        _ => return None,
+    })
+}
+
+// returns the attributes that exist, along with the versions of python packages
+async fn existing_attrs(
+    nixpkgs: &str,
+    attrs: &BTreeSet<String>,
+) -> Option<FxHashMap<String, Option<String>>> {
+    let stdout = Command::new(NIX)
+        .arg("eval")
+        .arg("--extra-experimental-features")
+        .arg("nix-command")
+        .arg("--impure")
+        .arg("--json")
+        .arg("--expr")
+        .arg(format!(
+            r#"let pkgs=import({nixpkgs}){{}};lib=pkgs.lib;path=lib.splitString ".";in lib.genAttrs(builtins.filter(attr:lib.hasAttrByPath(path attr)pkgs)[{}])(attr:let version=builtins.tryEval(let pkg=lib.getAttrFromPath(path attr)pkgs;in if lib.hasPrefix "python3Packages." attr&&builtins.isAttrs pkg then pkg.version or null else null);in if version.success&&builtins.isString version.value then version.value else null)"#,
+            attrs.iter().map(|attr| format!("{attr:?}")).join(" "),
+        ))
+        .get_stdout()
+        .await
+        .ok_inspect(|e| warn!("failed to validate inputs: {e}"))?;
+
+    serde_json::from_slice(&stdout).ok_inspect(|e| warn!("{e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::candidates;
+
+    #[test]
+    fn normalise() {
+        assert_eq!(candidates("pkg-config"), ["pkg-config", "pkg_config"]);
+        assert_eq!(candidates("PyYAML"), ["PyYAML", "pyyaml"]);
+        assert_eq!(
+            candidates("python-magic_fu"),
+            [
+                "python-magic_fu",
+                "python-magic-fu",
+                "python_magic_fu",
+                "magic_fu",
+            ],
+        );
+        assert_eq!(
+            candidates("python3Packages.opencv-python"),
+            [
+                "python3Packages.opencv-python",
+                "python3Packages.opencv_python",
+                "python3Packages.opencv4",
+            ],
+        );
+    }
+}